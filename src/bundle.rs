@@ -0,0 +1,175 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	bundles of CAS objects, as tar archives with a manifest
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Read, Write};
+
+use cas;
+use consts;
+use error::EnprotError;
+use etree::ParseOps;
+use tar;
+use toml;
+
+pub struct Bundle {
+    pub documents: Vec<String>,             // the documents it was made for
+    pub objects: BTreeMap<String, Vec<u8>>, // CAS objects by name
+}
+
+impl Bundle {
+    fn manifest(&self) -> String {
+        let quote = |s: &String| toml::Value::String(s.clone()).to_string();
+        let list = |items: Vec<String>| {
+            if items.is_empty() {
+                "[]".to_string()
+            } else {
+                format!("[\n    {},\n]", items.join(",\n    "))
+            }
+        };
+        // the hash algorithms the objects are named by
+        let hashes: BTreeSet<String> = self
+            .objects
+            .keys()
+            .filter_map(|id| cas::split_id(id).map(|(alg, _)| alg.to_string()))
+            .collect();
+        format!(
+            "version = {}\nhashes = {}\ndocuments = {}\nobjects = {}\n",
+            consts::BUNDLE_VERSION,
+            list(hashes.iter().map(quote).collect()),
+            list(self.documents.iter().map(quote).collect()),
+            list(self.objects.keys().map(quote).collect()),
+        )
+    }
+
+    // the manifest first, then the objects
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut builder = tar::Builder::new(writer);
+        let mut append = |path: &str, data: &[u8]| {
+            let mut header = tar::Header::new_ustar();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(0);
+            builder.append_data(&mut header, path, data)
+        };
+        append(consts::BUNDLE_MANIFEST, self.manifest().as_bytes())?;
+        for (id, blob) in &self.objects {
+            append(&format!("{}{}", consts::BUNDLE_OBJECTS, id), blob)?;
+        }
+        builder.into_inner()?.flush()
+    }
+
+    // reads a bundle, checking every object against the manifest and its name
+    pub fn read<R: Read>(reader: R, path: &str, paops: &ParseOps) -> Result<Bundle, EnprotError> {
+        let bundle_err = |msg: String| EnprotError::Bundle {
+            path: path.to_string(),
+            msg,
+        };
+        let io_err = |io| EnprotError::Io {
+            path: path.to_string(),
+            io,
+        };
+
+        let mut manifest = None;
+        let mut objects = BTreeMap::new();
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().map_err(io_err)? {
+            let mut entry = entry.map_err(io_err)?;
+            let name = entry.path().map_err(io_err)?.to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(io_err)?;
+            if name == consts::BUNDLE_MANIFEST {
+                let text = String::from_utf8(data)
+                    .map_err(|_| bundle_err("manifest is not UTF-8".to_string()))?;
+                manifest = Some(
+                    text.parse::<toml::Value>()
+                        .map_err(|e| bundle_err(format!("manifest: {}", e)))?,
+                );
+            } else if let Some(id) = name.strip_prefix(consts::BUNDLE_OBJECTS) {
+                objects.insert(id.to_string(), data);
+            } else {
+                return Err(bundle_err(format!("unexpected entry {}", name)));
+            }
+        }
+        let manifest = manifest.ok_or_else(|| bundle_err("no manifest".to_string()))?;
+
+        let version = manifest.get("version").and_then(|v| v.as_integer());
+        if version != Some(consts::BUNDLE_VERSION) {
+            return Err(bundle_err("unsupported manifest version".to_string()));
+        }
+        let strings = |key: &str| -> Result<Vec<String>, EnprotError> {
+            let items = manifest
+                .get(key)
+                .and_then(|v| v.as_array())
+                .ok_or_else(|| bundle_err(format!("manifest has no {} list", key)))?;
+            items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .map(|s| s.to_string())
+                        .ok_or_else(|| bundle_err(format!("{} must be strings", key)))
+                })
+                .collect()
+        };
+        let hashes = strings("hashes")?;
+        if let Some(alg) = hashes
+            .iter()
+            .find(|alg| !consts::VALID_CAS_HASH_ALGS.contains(&alg.as_str()))
+        {
+            return Err(bundle_err(format!("unsupported hash '{}'", alg)));
+        }
+        let documents = strings("documents")?;
+        let listed = strings("objects")?
+            .into_iter()
+            .collect::<BTreeSet<String>>();
+
+        // exactly the objects listed, each with the contents it is named after
+        for id in &listed {
+            if !objects.contains_key(id) {
+                return Err(bundle_err(format!("object {} is missing", id)));
+            }
+        }
+        for (id, blob) in &objects {
+            if !listed.contains(id) {
+                return Err(bundle_err(format!("object {} is not in the manifest", id)));
+            }
+            match cas::split_id(id) {
+                Some((alg, _)) if hashes.iter().any(|hash| hash == alg) => (),
+                _ => {
+                    return Err(bundle_err(format!(
+                        "object {} isn't named by a listed hash",
+                        id
+                    )))
+                }
+            }
+            if let Some(actual) = cas::misnamed(id, blob, paops)? {
+                return Err(bundle_err(format!(
+                    "object {} has contents hashing to {}",
+                    id, actual
+                )));
+            }
+        }
+        Ok(Bundle { documents, objects })
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	CAS objects as files in a directory
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use atomic;
+use consts;
+use toml;
+
+use super::CasBackend;
+
+// where objects live in a casdir: all in the casdir itself, or git-style
+// in subdirectories named after the first two hex digits of their hash
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Layout {
+    Flat,
+    Sharded,
+}
+
+impl Layout {
+    fn from_name(name: &str) -> Option<Layout> {
+        match name {
+            "flat" => Some(Layout::Flat),
+            "sharded" => Some(Layout::Sharded),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Layout::Flat => "flat",
+            Layout::Sharded => "sharded",
+        }
+    }
+
+    fn other(self) -> Layout {
+        match self {
+            Layout::Flat => Layout::Sharded,
+            Layout::Sharded => Layout::Flat,
+        }
+    }
+
+    fn path(self, casdir: &Path, id: &str) -> PathBuf {
+        let name = super::file_name(id);
+        match self {
+            Layout::Flat => casdir.join(name),
+            Layout::Sharded => casdir.join(&name[..2]).join(&name[2..]),
+        }
+    }
+}
+
+fn is_hex(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+pub struct DirBackend {
+    casdir: PathBuf,
+    new_layout: String, // layout of a casdir without a marker
+}
+
+impl DirBackend {
+    pub fn new(casdir: PathBuf, new_layout: &str) -> DirBackend {
+        DirBackend {
+            casdir,
+            new_layout: new_layout.to_string(),
+        }
+    }
+
+    // the layout recorded in the casdir marker, if there is one
+    fn recorded_layout(&self) -> io::Result<Option<Layout>> {
+        let path = self.casdir.join(consts::CAS_MARKER);
+        let marker_err = |msg: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid {}: {}", path.display(), msg),
+            )
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let table = text
+            .parse::<toml::Value>()
+            .map_err(|e| marker_err(e.to_string()))?;
+        match table.get("layout").and_then(|layout| layout.as_str()) {
+            Some(name) => Layout::from_name(name)
+                .map(Some)
+                .ok_or_else(|| marker_err(format!("unknown layout '{}'", name))),
+            None => Err(marker_err("missing 'layout'".to_string())),
+        }
+    }
+
+    // the layout of an existing casdir; one without a marker predates them
+    fn layout(&self) -> io::Result<Layout> {
+        Ok(self.recorded_layout()?.unwrap_or(Layout::Flat))
+    }
+
+    // the layout new objects go into, recording it for a casdir without one
+    fn layout_for_put(&self) -> io::Result<Layout> {
+        if let Some(layout) = self.recorded_layout()? {
+            return Ok(layout);
+        }
+        let layout = Layout::from_name(&self.new_layout).unwrap_or(Layout::Flat);
+        if layout != Layout::Flat {
+            let marker = format!("layout = \"{}\"\n", layout.name());
+            atomic::write(
+                &self.casdir.join(consts::CAS_MARKER),
+                marker.as_bytes(),
+                None,
+            )?;
+        }
+        Ok(layout)
+    }
+
+    // the path of an existing object, in either layout
+    fn find(&self, id: &str) -> io::Result<Option<PathBuf>> {
+        let layout = self.layout()?;
+        for layout in &[layout, layout.other()] {
+            let path = layout.path(&self.casdir, id);
+            if path.is_file() {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl CasBackend for DirBackend {
+    fn get(&self, id: &str) -> io::Result<Vec<u8>> {
+        match self.find(id)? {
+            Some(path) => fs::read(path),
+            // report the object missing where it belongs
+            None => fs::read(self.layout()?.path(&self.casdir, id)),
+        }
+    }
+
+    fn put(&self, id: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.layout_for_put()?.path(&self.casdir, id);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // never leave a partial object under its final name
+        atomic::write(&path, data, None)
+    }
+
+    fn exists(&self, id: &str) -> io::Result<bool> {
+        Ok(self.find(id)?.is_some())
+    }
+
+    fn size(&self, id: &str) -> io::Result<u64> {
+        match self.find(id)? {
+            Some(path) => fs::metadata(path).map(|meta| meta.len()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no such object")),
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.casdir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let file_type = entry.file_type()?;
+            if file_type.is_file() {
+                ids.extend(super::id_from_file_name(&name));
+            } else if file_type.is_dir() && name.len() == 2 && is_hex(&name) {
+                for sub in fs::read_dir(entry.path())? {
+                    let sub = sub?;
+                    let sub_name = sub.file_name().to_string_lossy().into_owned();
+                    if sub.file_type()?.is_file() {
+                        ids.extend(super::id_from_file_name(&(name.clone() + &sub_name)));
+                    }
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn delete(&self, id: &str) -> io::Result<()> {
+        match self.find(id)? {
+            Some(path) => fs::remove_file(path),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no such object")),
+        }
+    }
+
+    // read-only directories hold shared stores; permission bits don't tell
+    // about ACLs, read-only mounts or other owners, so try creating a file
+    fn writable(&self) -> bool {
+        match atomic::create_temp(&self.casdir, OsStr::new("writable")) {
+            Ok((path, _)) => fs::remove_file(path).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn url(&self) -> String {
+        format!("file:{}", self.casdir.display())
+    }
+
+    fn dirs(&self) -> Vec<PathBuf> {
+        vec![self.casdir.clone()]
+    }
+}
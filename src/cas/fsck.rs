@@ -0,0 +1,176 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	consistency checks of a whole CAS
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use consts;
+use error::EnprotError;
+use etree::ParseOps;
+
+use super::CasBackend;
+
+pub enum Problem {
+    Mismatch(String), // contents hash to this instead
+    Empty,
+    Unreadable(io::Error),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Mismatch(actual) => write!(f, "contents hash to {}", actual),
+            Problem::Empty => write!(f, "empty object"),
+            Problem::Unreadable(e) => write!(f, "unreadable: {}", e),
+        }
+    }
+}
+
+pub struct Finding {
+    pub path: String,
+    pub problem: Problem,
+    pub moved_to: Option<PathBuf>, // where it was quarantined
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub checked: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    // whether every problem found has been dealt with
+    pub fn clean(&self) -> bool {
+        self.findings
+            .iter()
+            .all(|finding| finding.moved_to.is_some())
+    }
+}
+
+fn is_hex(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// what is wrong with an object, if anything
+fn check_object(id: &str, blob: &[u8], paops: &ParseOps) -> Result<Option<Problem>, EnprotError> {
+    if blob.is_empty() {
+        return Ok(Some(Problem::Empty));
+    }
+    Ok(super::misnamed(id, blob, paops)?.map(Problem::Mismatch))
+}
+
+// checks every object in a casdir, optionally moving bad ones to lost+found;
+// anything not named like an object is none of fsck's business
+pub fn check_dir(casdir: &Path, paops: &ParseOps, quarantine: bool) -> Result<Report, EnprotError> {
+    let io_err = |path: &Path| {
+        let path = path.display().to_string();
+        move |io| EnprotError::Io { path, io }
+    };
+    let mut report = Report::default();
+    let mut objects = Vec::new();
+    for entry in fs::read_dir(casdir).map_err(io_err(casdir))? {
+        let entry = entry.map_err(io_err(casdir))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let file_type = entry.file_type().map_err(io_err(&entry.path()))?;
+        if file_type.is_dir() && name.len() == 2 && is_hex(&name) {
+            // a shard of the sharded layout
+            for sub in fs::read_dir(entry.path()).map_err(io_err(&entry.path()))? {
+                let sub = sub.map_err(io_err(&entry.path()))?;
+                let full_name = name.clone() + &sub.file_name().to_string_lossy();
+                if sub.file_type().map_err(io_err(&sub.path()))?.is_file() {
+                    if let Some(id) = super::id_from_file_name(&full_name) {
+                        objects.push((sub.path(), full_name, id));
+                    }
+                }
+            }
+        } else if file_type.is_file() {
+            if let Some(id) = super::id_from_file_name(&name) {
+                objects.push((entry.path(), name, id));
+            }
+        }
+    }
+    objects.sort();
+
+    for (path, full_name, id) in objects {
+        report.checked += 1;
+        let problem = match fs::read(&path) {
+            Ok(blob) => check_object(&id, &blob, paops)?,
+            Err(e) => Some(Problem::Unreadable(e)),
+        };
+        if let Some(problem) = problem {
+            let moved_to = if quarantine {
+                Some(move_to_lost_found(casdir, &path, &full_name).map_err(io_err(&path))?)
+            } else {
+                None
+            };
+            report.findings.push(Finding {
+                path: path.display().to_string(),
+                problem,
+                moved_to,
+            });
+        }
+    }
+    Ok(report)
+}
+
+// moves a file out of the way under its full name, never replacing another
+fn move_to_lost_found(casdir: &Path, path: &Path, name: &str) -> io::Result<PathBuf> {
+    let lost_found = casdir.join(consts::CAS_LOST_FOUND);
+    fs::create_dir_all(&lost_found)?;
+    let mut dest = lost_found.join(name);
+    let mut i = 0;
+    while fs::symlink_metadata(&dest).is_ok() {
+        i += 1;
+        dest = lost_found.join(format!("{}.{}", name, i));
+    }
+    fs::rename(path, &dest)?;
+    Ok(dest)
+}
+
+// checks the objects of a store that can only be accessed through its backend
+pub fn check_store(backend: &dyn CasBackend, paops: &ParseOps) -> Result<Report, EnprotError> {
+    let mut report = Report::default();
+    let ids = backend.list().map_err(|io| EnprotError::Io {
+        path: backend.url(),
+        io,
+    })?;
+    for id in ids {
+        report.checked += 1;
+        let problem = match backend.get(&id) {
+            Ok(blob) => check_object(&id, &blob, paops)?,
+            Err(e) => Some(Problem::Unreadable(e)),
+        };
+        if let Some(problem) = problem {
+            report.findings.push(Finding {
+                path: format!("{} in {}", id, backend.url()),
+                problem,
+                moved_to: None,
+            });
+        }
+    }
+    Ok(report)
+}
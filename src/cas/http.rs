@@ -0,0 +1,292 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	CAS objects on an HTTP server
+//
+//	GET, PUT, HEAD and DELETE of the base URL followed by the object name
+//	read, write, test and remove objects. GET of the base URL itself lists
+//	the objects, one name per line.
+
+use std::io;
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::time::Duration;
+
+use super::CasBackend;
+
+const TIMEOUT_SECS: u64 = 30;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// the status and body of a raw HTTP/1.1 response
+fn parse_response(response: &[u8], head: bool) -> io::Result<(u16, Vec<u8>)> {
+    let end = find(response, b"\r\n\r\n").ok_or_else(|| invalid("truncated HTTP response"))?;
+    let header = String::from_utf8_lossy(&response[..end]);
+    let mut lines = header.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| invalid("invalid HTTP status line"))?;
+
+    let mut chunked = false;
+    let mut length = None;
+    for line in lines {
+        if let Some(colon) = line.find(':') {
+            let name = line[..colon].trim().to_ascii_lowercase();
+            let value = line[colon + 1..].trim();
+            if name == "content-length" {
+                length = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| invalid("invalid Content-Length"))?,
+                );
+            } else if name == "transfer-encoding" {
+                chunked = value.to_ascii_lowercase().contains("chunked");
+            }
+        }
+    }
+
+    let body = &response[end + 4..];
+    if head {
+        return Ok((status, Vec::new()));
+    }
+    let body = if chunked {
+        dechunk(body)?
+    } else if let Some(length) = length {
+        if body.len() < length {
+            return Err(invalid("truncated HTTP response"));
+        }
+        body[..length].to_vec()
+    } else {
+        body.to_vec()
+    };
+    Ok((status, body))
+}
+
+// decode a chunked transfer encoding
+fn dechunk(mut data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let eol = find(data, b"\r\n").ok_or_else(|| invalid("truncated HTTP chunk"))?;
+        let size = String::from_utf8_lossy(&data[..eol]);
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or("").trim(), 16)
+            .map_err(|_| invalid("invalid HTTP chunk size"))?;
+        data = &data[eol + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if data.len() < size + 2 {
+            return Err(invalid("truncated HTTP chunk"));
+        }
+        body.extend_from_slice(&data[..size]);
+        data = &data[size + 2..];
+    }
+}
+
+fn check_status(status: u16) -> io::Result<()> {
+    match status {
+        200..=299 => Ok(()),
+        404 => Err(io::Error::new(io::ErrorKind::NotFound, "no such object")),
+        _ => Err(io::Error::other(format!("HTTP status {}", status))),
+    }
+}
+
+pub struct HttpBackend {
+    authority: String, // host[:port]
+    host: String,
+    port: u16,
+    path: String, // always ends with a slash
+}
+
+impl HttpBackend {
+    pub fn new(url: &str) -> Result<HttpBackend, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| "expected an http:// URL".to_string())?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        // an IPv6 address is in brackets, as it contains colons itself
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let end = bracketed
+                    .find(']')
+                    .ok_or_else(|| "unterminated IPv6 address".to_string())?;
+                (&bracketed[..end], &bracketed[end + 1..])
+            }
+            None => match authority.rfind(':') {
+                Some(colon) => (&authority[..colon], &authority[colon..]),
+                None => (authority, ""),
+            },
+        };
+        let port = match port {
+            "" => 80,
+            _ => port
+                .strip_prefix(':')
+                .and_then(|port| port.parse::<u16>().ok())
+                .ok_or_else(|| "invalid port".to_string())?,
+        };
+        if host.is_empty() {
+            return Err("missing host".to_string());
+        }
+        let mut path = path.to_string();
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        Ok(HttpBackend {
+            authority: authority.to_string(),
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    // one request per connection keeps this simple
+    fn request(&self, method: &str, id: &str, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECS)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(TIMEOUT_SECS)))?;
+        write!(
+            stream,
+            "{} {}{} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            self.path,
+            id,
+            self.authority,
+            body.len()
+        )?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_response(&response, method == "HEAD")
+    }
+}
+
+impl CasBackend for HttpBackend {
+    fn get(&self, id: &str) -> io::Result<Vec<u8>> {
+        let (status, body) = self.request("GET", id, &[])?;
+        check_status(status)?;
+        Ok(body)
+    }
+
+    fn put(&self, id: &str, data: &[u8]) -> io::Result<()> {
+        let (status, _) = self.request("PUT", id, data)?;
+        check_status(status)
+    }
+
+    fn exists(&self, id: &str) -> io::Result<bool> {
+        let (status, _) = self.request("HEAD", id, &[])?;
+        match check_status(status) {
+            Ok(()) => Ok(true),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let (status, body) = self.request("GET", "", &[])?;
+        check_status(status)?;
+        // the names end up in request paths, so only accept valid ones
+        String::from_utf8_lossy(&body)
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| match super::split_id(line) {
+                Some(_) => Ok(line.to_string()),
+                None => Err(invalid(&format!(
+                    "invalid object name '{}' in listing",
+                    line
+                ))),
+            })
+            .collect()
+    }
+
+    fn delete(&self, id: &str) -> io::Result<()> {
+        let (status, _) = self.request("DELETE", id, &[])?;
+        check_status(status)
+    }
+
+    fn url(&self) -> String {
+        format!("http://{}{}", self.authority, self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_response_length() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello, world";
+        assert_eq!(
+            parse_response(response, false).unwrap(),
+            (200, b"hello".to_vec())
+        );
+        assert_eq!(
+            parse_response(b"HTTP/1.1 404 Not Found\r\n\r\n", true).unwrap(),
+            (404, Vec::new())
+        );
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhe", false).is_err());
+    }
+
+    #[test]
+    fn http_response_chunked() {
+        let response =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n7;x=y\r\n, world\r\n0\r\n\r\n";
+        assert_eq!(
+            parse_response(response, false).unwrap(),
+            (200, b"hello, world".to_vec())
+        );
+    }
+
+    #[test]
+    fn http_url() {
+        let backend = HttpBackend::new("http://127.0.0.1:8080/cas").unwrap();
+        assert_eq!(backend.host, "127.0.0.1");
+        assert_eq!(backend.port, 8080);
+        assert_eq!(backend.url(), "http://127.0.0.1:8080/cas/");
+        assert_eq!(HttpBackend::new("http://example.com").unwrap().port, 80);
+        assert!(HttpBackend::new("http://:80/").is_err());
+        assert!(HttpBackend::new("http://host:port/").is_err());
+
+        let backend = HttpBackend::new("http://[::1]:8080/cas/").unwrap();
+        assert_eq!(backend.host, "::1");
+        assert_eq!(backend.port, 8080);
+        assert_eq!(backend.url(), "http://[::1]:8080/cas/");
+        assert_eq!(HttpBackend::new("http://[fe80::1]/").unwrap().port, 80);
+        assert!(HttpBackend::new("http://[::1/").is_err());
+        assert!(HttpBackend::new("http://[::1]x/").is_err());
+    }
+}
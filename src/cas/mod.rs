@@ -0,0 +1,367 @@
+// Copyright (c) 2018-2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	content addressed storage
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use consts;
+use crypto;
+use error::EnprotError;
+use etree::ParseOps;
+
+mod dir;
+pub mod fsck;
+mod http;
+mod search;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use self::dir::DirBackend;
+use self::http::HttpBackend;
+pub use self::search::SearchPath;
+#[cfg(feature = "sqlite")]
+use self::sqlite::SqliteBackend;
+
+/// A store of CAS objects, each named by the hex digest of its contents.
+///
+/// Backends only move bytes around; digests are computed and checked by
+/// the callers.
+pub trait CasBackend {
+    /// Reads an object, failing with `io::ErrorKind::NotFound` if there is
+    /// no such object.
+    fn get(&self, id: &str) -> io::Result<Vec<u8>>;
+    /// Writes an object, replacing any object of the same name.
+    fn put(&self, id: &str, data: &[u8]) -> io::Result<()>;
+    /// Checks if an object exists.
+    fn exists(&self, id: &str) -> io::Result<bool>;
+    /// The size of an object in bytes, failing like `get` if there is no
+    /// such object.
+    fn size(&self, id: &str) -> io::Result<u64> {
+        self.get(id).map(|data| data.len() as u64)
+    }
+    /// The names of all objects.
+    fn list(&self) -> io::Result<Vec<String>>;
+    /// Removes an object.
+    fn delete(&self, id: &str) -> io::Result<()>;
+    /// Whether new objects can be written.
+    fn writable(&self) -> bool {
+        true
+    }
+    /// The part of a composite store that new objects are written to and
+    /// that is the only one cleaned up, or `None` for a single store.
+    fn write_store(&self) -> io::Result<Option<&dyn CasBackend>> {
+        Ok(None)
+    }
+    /// Where the objects are kept, for messages.
+    fn url(&self) -> String;
+    /// Where an object is kept, for messages.
+    fn url_of(&self, _id: &str) -> String {
+        self.url()
+    }
+    /// The directories holding the objects as plain files, if any.
+    fn dirs(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+// a backend from a URL such as file:cas/, sqlite:secrets.db or
+// http://127.0.0.1:8080/cas/; relative paths are taken from `base`
+
+pub fn open(url: &str, base: &Path, new_layout: &str) -> Result<Box<dyn CasBackend>, EnprotError> {
+    let url_err = |msg: String| EnprotError::CasUrl {
+        url: url.to_string(),
+        msg,
+    };
+    if url.starts_with("http://") {
+        return Ok(Box::new(HttpBackend::new(url).map_err(url_err)?));
+    }
+    if let Some(path) = url.strip_prefix("sqlite:") {
+        return open_sqlite(&base.join(path)).map_err(url_err);
+    }
+    let path = match url
+        .strip_prefix("file://")
+        .or_else(|| url.strip_prefix("file:"))
+    {
+        Some(path) => base.join(path),
+        None => {
+            return Err(url_err(
+                "expected a file:, sqlite: or http:// URL".to_string(),
+            ))
+        }
+    };
+    if !path.is_dir() {
+        return Err(url_err(format!("{} is not a directory", path.display())));
+    }
+    Ok(Box::new(DirBackend::new(path, new_layout)))
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &Path) -> Result<Box<dyn CasBackend>, String> {
+    let backend = SqliteBackend::open(path).map_err(|e| e.to_string())?;
+    Ok(Box::new(backend))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(_path: &Path) -> Result<Box<dyn CasBackend>, String> {
+    Err("built without SQLite support (cargo feature \"sqlite\")".to_string())
+}
+
+// the backend in paops, or else the directory store in paops.casdir
+fn with_backend<T, F>(paops: &ParseOps, f: F) -> T
+where
+    F: FnOnce(&dyn CasBackend) -> T,
+{
+    match paops.cas {
+        Some(ref backend) => f(backend.as_ref()),
+        None => f(&DirBackend::new(paops.casdir.clone(), &paops.cas_layout)),
+    }
+}
+
+// CAS identifiers are hex digests, prefixed with the hash algorithm and a
+// colon unless it is sha3-256: "sha256:e3b0c442..."
+
+pub fn hex_len(alg: &str) -> Option<usize> {
+    match alg {
+        "sha3-256" | "sha256" => Some(64),
+        "sha3-512" | "sha512" | "blake2b" => Some(128),
+        _ => None,
+    }
+}
+
+// the hash algorithm and the digest of an identifier
+pub fn split_id(id: &str) -> Option<(&str, &str)> {
+    let (alg, digest) = match id.find(':') {
+        Some(i) => (&id[..i], &id[i + 1..]),
+        None => (consts::DEFAULT_CAS_HASH_ALG, id),
+    };
+    if hex_len(alg) != Some(digest.len()) || hex::decode(digest).is_err() {
+        return None;
+    }
+    Some((alg, digest))
+}
+
+pub fn make_id(alg: &str, digest: &str) -> String {
+    if alg == consts::DEFAULT_CAS_HASH_ALG {
+        digest.to_string()
+    } else {
+        format!("{}:{}", alg, digest)
+    }
+}
+
+// objects are kept in files named after their digest, followed by the
+// algorithm as an extension unless it is sha3-256
+pub fn file_name(id: &str) -> String {
+    match id.find(':') {
+        Some(i) => format!("{}.{}", &id[i + 1..], &id[..i]),
+        None => id.to_string(),
+    }
+}
+
+pub fn id_from_file_name(name: &str) -> Option<String> {
+    let id = match name.find('.') {
+        Some(i) => format!("{}:{}", &name[i + 1..], &name[..i]),
+        None => name.to_string(),
+    };
+    if split_id(&id).is_some() {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+fn check_id(hexhash: &str) -> Result<(&str, &str), EnprotError> {
+    split_id(hexhash).ok_or_else(|| EnprotError::CasInvalidId(hexhash.to_string()))
+}
+
+pub fn load(hexhash: &str, paops: &mut ParseOps) -> Result<Vec<u8>, EnprotError> {
+    // check that it is valid
+    check_id(hexhash)?;
+
+    let cas_err = |io| EnprotError::Cas {
+        hash: hexhash.to_string(),
+        io,
+    };
+
+    let (blob, url) = with_backend(paops, |backend| {
+        let blob = backend.get(hexhash).map_err(cas_err)?;
+        Ok((blob, backend.url_of(hexhash)))
+    })?;
+    if paops.verbose {
+        paops.notes.push(format!(
+            "cas::load(): {} bytes of {} from {}",
+            blob.len(),
+            hexhash,
+            url
+        ));
+    }
+
+    // verify hash just because
+    if let Some(verify) = misnamed(hexhash, &blob, paops)? {
+        return Err(EnprotError::CasMismatch {
+            hash: hexhash.to_string(),
+            actual: verify,
+        });
+    }
+
+    Ok(blob)
+}
+
+// the name of an object: the hash of its contents, or their HMAC under the
+// CAS key so that names can't be used to confirm guesses of the contents
+pub fn object_id(alg: &str, blob: &[u8], paops: &ParseOps) -> Result<String, EnprotError> {
+    let digest = match paops.cas_key {
+        Some(ref key) => crypto::hexmac(alg, key, blob, &paops.policy)?,
+        None => crypto::hexdigest(alg, blob, &paops.policy)?,
+    };
+    Ok(make_id(alg, &digest))
+}
+
+// the name an object should have if it isn't named after its contents,
+// hashed like its name; objects named by plain hash stay valid in a keyed CAS
+pub fn misnamed(
+    hexhash: &str,
+    blob: &[u8],
+    paops: &ParseOps,
+) -> Result<Option<String>, EnprotError> {
+    let (alg, digest) = check_id(hexhash)?;
+    let expected = object_id(alg, blob, paops)?;
+    if hexhash == expected
+        || (paops.cas_key.is_some() && digest == crypto::hexdigest(alg, blob, &paops.policy)?)
+    {
+        return Ok(None);
+    }
+    Ok(Some(expected))
+}
+
+pub fn save(blob: Vec<u8>, paops: &mut ParseOps) -> Result<String, EnprotError> {
+    let hexhash = object_id(&paops.cas_hash, &blob, paops)?;
+    save_as(&hexhash, blob, paops)?;
+    Ok(hexhash)
+}
+
+// stores an object under a name already checked with misnamed()
+pub fn save_as(hexhash: &str, blob: Vec<u8>, paops: &mut ParseOps) -> Result<(), EnprotError> {
+    let hexhash = hexhash.to_string();
+    let cas_err = |io| EnprotError::Cas {
+        hash: hexhash.clone(),
+        io,
+    };
+
+    let mut notes = Vec::new();
+    let saved = with_backend(paops, |backend| {
+        // check if it exists, and is intact
+        if backend.exists(&hexhash).map_err(cas_err)? {
+            match backend.get(&hexhash) {
+                Ok(ref existing) if *existing == blob => {
+                    notes.push(format!(
+                        "cas:save(): {} already exists in {}. Exiting.",
+                        hexhash,
+                        backend.url_of(&hexhash)
+                    ));
+                    return Ok(());
+                }
+                _ => notes.push(format!(
+                    "cas:save(): {} in {} is corrupt, repairing.",
+                    hexhash,
+                    backend.url_of(&hexhash)
+                )),
+            }
+        }
+
+        if paops.dry_run {
+            notes.push(format!(
+                "cas:save(): dry run, not writing {} to {}",
+                hexhash,
+                backend.url()
+            ));
+            return Ok(());
+        }
+
+        backend.put(&hexhash, &blob).map_err(cas_err)?;
+        notes.push(format!(
+            "cas:save(): {} bytes to {} in {}",
+            blob.len(),
+            hexhash,
+            backend.url_of(&hexhash)
+        ));
+        Ok(())
+    });
+    if paops.verbose {
+        paops.notes.extend(notes);
+    }
+    saved
+}
+
+// the size of an object, without reading it where the backend allows
+pub fn size(hexhash: &str, paops: &ParseOps) -> Result<u64, EnprotError> {
+    check_id(hexhash)?;
+    with_backend(paops, |backend| {
+        backend.size(hexhash).map_err(|io| EnprotError::Cas {
+            hash: hexhash.to_string(),
+            io,
+        })
+    })
+}
+
+// the names of the objects in the store written to, and where it is; the
+// other stores of a search path may be shared, so are never cleaned up
+pub fn list_written(paops: &ParseOps) -> Result<(Vec<String>, String), EnprotError> {
+    with_backend(paops, |backend| {
+        let store = backend
+            .write_store()
+            .map_err(|io| EnprotError::Io {
+                path: backend.url(),
+                io,
+            })?
+            .unwrap_or(backend);
+        let ids = store.list().map_err(|io| EnprotError::Io {
+            path: store.url(),
+            io,
+        })?;
+        Ok((ids, store.url()))
+    })
+}
+
+pub fn delete(hexhash: &str, paops: &mut ParseOps) -> Result<(), EnprotError> {
+    let url = with_backend(paops, |backend| {
+        let url = backend.url_of(hexhash);
+        backend.delete(hexhash).map_err(|io| EnprotError::Cas {
+            hash: hexhash.to_string(),
+            io,
+        })?;
+        Ok(url)
+    })?;
+    if paops.verbose {
+        paops
+            .notes
+            .push(format!("cas::delete(): {} from {}", hexhash, url));
+    }
+    Ok(())
+}
+
+// where the objects are kept
+pub fn url(paops: &ParseOps) -> String {
+    with_backend(paops, |backend| backend.url())
+}
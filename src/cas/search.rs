@@ -0,0 +1,135 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	several CAS stores searched in order
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::PathBuf;
+
+use super::CasBackend;
+
+pub struct SearchPath {
+    stores: Vec<Box<dyn CasBackend>>,
+    write: Option<usize>, // designated store for writes
+}
+
+impl SearchPath {
+    pub fn new(stores: Vec<Box<dyn CasBackend>>, write: Option<usize>) -> SearchPath {
+        SearchPath { stores, write }
+    }
+
+    // the designated store, or else the first writable one
+    fn writer(&self) -> io::Result<&dyn CasBackend> {
+        let store = match self.write {
+            Some(i) => self.stores.get(i),
+            None => self.stores.iter().find(|store| store.writable()),
+        };
+        store.map(|store| store.as_ref()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("none of {} is writable", self.url()),
+            )
+        })
+    }
+}
+
+impl CasBackend for SearchPath {
+    fn get(&self, id: &str) -> io::Result<Vec<u8>> {
+        let mut missing = None;
+        for store in &self.stores {
+            match store.get(id) {
+                Ok(data) => return Ok(data),
+                Err(e) => {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        return Err(e);
+                    }
+                    missing.get_or_insert(e);
+                }
+            }
+        }
+        Err(missing.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such object")))
+    }
+
+    fn put(&self, id: &str, data: &[u8]) -> io::Result<()> {
+        self.writer()?.put(id, data)
+    }
+
+    fn exists(&self, id: &str) -> io::Result<bool> {
+        for store in &self.stores {
+            if store.exists(id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn size(&self, id: &str) -> io::Result<u64> {
+        for store in &self.stores {
+            if store.exists(id)? {
+                return store.size(id);
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, "no such object"))
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut ids = BTreeSet::new();
+        for store in &self.stores {
+            ids.extend(store.list()?);
+        }
+        Ok(ids.into_iter().collect())
+    }
+
+    // only from the store objects are written to; the others may be shared
+    fn delete(&self, id: &str) -> io::Result<()> {
+        self.writer()?.delete(id)
+    }
+
+    fn writable(&self) -> bool {
+        self.writer().is_ok()
+    }
+
+    fn write_store(&self) -> io::Result<Option<&dyn CasBackend>> {
+        self.writer().map(Some)
+    }
+
+    fn url(&self) -> String {
+        let urls: Vec<String> = self.stores.iter().map(|store| store.url()).collect();
+        urls.join(" ")
+    }
+
+    // the first store with the object, or else the one it would be written to
+    fn url_of(&self, id: &str) -> String {
+        self.stores
+            .iter()
+            .find(|store| store.exists(id).unwrap_or(false))
+            .map(|store| store.url())
+            .or_else(|| self.writer().ok().map(|store| store.url()))
+            .unwrap_or_else(|| self.url())
+    }
+
+    fn dirs(&self) -> Vec<PathBuf> {
+        self.stores.iter().flat_map(|store| store.dirs()).collect()
+    }
+}
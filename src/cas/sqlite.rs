@@ -0,0 +1,135 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	CAS objects in a single SQLite database file
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::CasBackend;
+
+fn sql_err(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no such object")
+}
+
+pub struct SqliteBackend {
+    conn: Connection,
+    path: PathBuf,
+}
+
+impl SqliteBackend {
+    // open the database, creating it if needed
+    pub fn open(path: &Path) -> io::Result<SqliteBackend> {
+        let conn = Connection::open(path).map_err(sql_err)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS objects (id TEXT PRIMARY KEY, data BLOB NOT NULL)",
+        )
+        .map_err(sql_err)?;
+        Ok(SqliteBackend {
+            conn,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl CasBackend for SqliteBackend {
+    fn get(&self, id: &str) -> io::Result<Vec<u8>> {
+        self.conn
+            .query_row(
+                "SELECT data FROM objects WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sql_err)?
+            .ok_or_else(not_found)
+    }
+
+    fn put(&self, id: &str, data: &[u8]) -> io::Result<()> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO objects (id, data) VALUES (?1, ?2)",
+                params![id, data],
+            )
+            .map_err(sql_err)?;
+        Ok(())
+    }
+
+    fn exists(&self, id: &str) -> io::Result<bool> {
+        let found: Option<i64> = self
+            .conn
+            .query_row("SELECT 1 FROM objects WHERE id = ?1", params![id], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(sql_err)?;
+        Ok(found.is_some())
+    }
+
+    fn size(&self, id: &str) -> io::Result<u64> {
+        let size: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT length(data) FROM objects WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sql_err)?;
+        size.map(|size| size as u64).ok_or_else(not_found)
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM objects ORDER BY id")
+            .map_err(sql_err)?;
+        let ids = stmt
+            .query_map(params![], |row| row.get(0))
+            .map_err(sql_err)?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(sql_err)?;
+        Ok(ids)
+    }
+
+    fn delete(&self, id: &str) -> io::Result<()> {
+        let deleted = self
+            .conn
+            .execute("DELETE FROM objects WHERE id = ?1", params![id])
+            .map_err(sql_err)?;
+        if deleted == 0 {
+            return Err(not_found());
+        }
+        Ok(())
+    }
+
+    fn url(&self) -> String {
+        format!("sqlite:{}", self.path.display())
+    }
+}
@@ -33,7 +33,9 @@ use botan::CipherDirection;
 use phf::phf_map;
 use std::marker::PhantomData;
 
+use consts;
 use crypto;
+use error::EnprotError;
 
 pub static BOTAN_CIPHER_ALG_MAP: phf::Map<&'static str, &'static str> = phf_map! {
     "aes-256-siv" => "AES-256/SIV",
@@ -51,7 +53,7 @@ pub trait SymmetricCipher {
         iv: &[u8],
         ad: &[u8],
         data: &[u8],
-    ) -> Result<Vec<u8>, &'static str>;
+    ) -> Result<Vec<u8>, EnprotError>;
 
     fn process(
         &self,
@@ -60,16 +62,19 @@ pub trait SymmetricCipher {
         ad: &[u8],
         data: &[u8],
         policy: &Box<dyn crypto::CryptoPolicy>,
-    ) -> Result<Vec<u8>, &'static str> {
+    ) -> Result<Vec<u8>, EnprotError> {
         policy.check_cipher(self.alg(), key, iv, ad)?;
         self._process(key, iv, ad, data)
     }
 }
 
-fn to_botan_cipher(alg: &str) -> Result<&'static str, &'static str> {
+fn to_botan_cipher(alg: &str) -> Result<&'static str, EnprotError> {
     Ok(BOTAN_CIPHER_ALG_MAP
         .get::<str>(alg)
-        .ok_or("Unrecognized cipher")?)
+        .ok_or_else(|| EnprotError::InvalidAlgorithm {
+            kind: "cipher",
+            alg: alg.to_string(),
+        })?)
 }
 
 struct BotanCipher {
@@ -81,12 +86,12 @@ struct BotanCipher {
 }
 
 impl BotanCipher {
-    fn create(alg: &str, direction: CipherDirection) -> Result<Self, &'static str> {
+    fn create(alg: &str, direction: CipherDirection) -> Result<Self, EnprotError> {
         let obj = botan::Cipher::new(to_botan_cipher(alg)?, direction)
-            .map_err(|_| "Botan error creating cipher")?;
+            .map_err(|_| EnprotError::Crypto("Botan error creating cipher"))?;
         let keyspec = obj
             .key_spec()
-            .map_err(|_| "Botan error retrieving key spec")?;
+            .map_err(|_| EnprotError::Crypto("Botan error retrieving key spec"))?;
         Ok(BotanCipher {
             alg: alg.to_string(),
             nonce_len: obj.default_nonce_length(),
@@ -120,16 +125,16 @@ impl SymmetricCipher for BotanCipher {
         iv: &[u8],
         ad: &[u8],
         data: &[u8],
-    ) -> Result<Vec<u8>, &'static str> {
+    ) -> Result<Vec<u8>, EnprotError> {
         self.obj
             .set_key(key)
-            .map_err(|_| "Botan error setting cipher key")?;
+            .map_err(|_| EnprotError::Crypto("Botan error setting cipher key"))?;
         self.obj
             .set_associated_data(ad)
-            .map_err(|_| "Botan error setting AD")?;
+            .map_err(|_| EnprotError::Crypto("Botan error setting AD"))?;
         self.obj
             .process(iv, data)
-            .map_err(|_| "Botan error processing cipher data")
+            .map_err(|_| EnprotError::Crypto("Botan error processing cipher data"))
     }
 }
 
@@ -149,7 +154,7 @@ impl<C> AESGCMSIVCipher<C>
 where
     C: BlockCipher<BlockSize = U16, ParBlocks = U8>,
 {
-    fn create(direction: CipherDirection) -> Result<Self, &'static str> {
+    fn create(direction: CipherDirection) -> Result<Self, EnprotError> {
         Ok(AESGCMSIVCipher {
             alg: format!("aes-{}-gcm-siv", C::KeySize::to_usize() * 8),
             nonce_len: <AesGcmSiv<C> as Aead>::NonceSize::to_usize(),
@@ -187,7 +192,10 @@ where
         iv: &[u8],
         ad: &[u8],
         data: &[u8],
-    ) -> Result<Vec<u8>, &'static str> {
+    ) -> Result<Vec<u8>, EnprotError> {
+        if key.len() != self.key_len_max || iv.len() != self.nonce_len {
+            return Err(EnprotError::Crypto("Invalid key or IV length"));
+        }
         let obj = AesGcmSiv::<C>::new(GenericArray::clone_from_slice(key));
         match self.direction {
             CipherDirection::Encrypt => obj
@@ -195,32 +203,64 @@ where
                     GenericArray::from_slice(iv),
                     aead::Payload { msg: data, aad: ad },
                 )
-                .map_err(|_| "Failed to encrypt"),
+                .map_err(|_| EnprotError::Crypto("Failed to encrypt")),
             CipherDirection::Decrypt => obj
                 .decrypt(
                     GenericArray::from_slice(iv),
                     aead::Payload { msg: data, aad: ad },
                 )
-                .map_err(|_| "Failed to decrypt"),
+                .map_err(|_| EnprotError::Crypto("Failed to decrypt")),
         }
     }
 }
 
-fn create(alg: &str, direction: CipherDirection) -> Result<Box<dyn SymmetricCipher>, &'static str> {
+fn create(alg: &str, direction: CipherDirection) -> Result<Box<dyn SymmetricCipher>, EnprotError> {
     match alg {
         "aes-256-gcm-siv" => Ok(Box::new(AESGCMSIVCipher::<Aes256>::create(direction)?)),
         _ => Ok(Box::new(BotanCipher::create(alg, direction)?)),
     }
 }
 
-pub fn encryption(alg: &str) -> Result<Box<dyn SymmetricCipher>, &'static str> {
+pub fn encryption(alg: &str) -> Result<Box<dyn SymmetricCipher>, EnprotError> {
     create(alg, CipherDirection::Encrypt)
 }
 
-pub fn decryption(alg: &str) -> Result<Box<dyn SymmetricCipher>, &'static str> {
+pub fn decryption(alg: &str) -> Result<Box<dyn SymmetricCipher>, EnprotError> {
     create(alg, CipherDirection::Decrypt)
 }
 
+// wrap a data key under a key-encryption key, as the nonce and ciphertext;
+// the nonce is a MAC of the data key, so that a key-encryption key without
+// a salt only repeats it for the same data key
+
+pub fn wrap_key(
+    kek: &[u8],
+    key: &[u8],
+    policy: &Box<dyn crypto::CryptoPolicy>,
+) -> Result<Vec<u8>, EnprotError> {
+    let enc = encryption(consts::KEY_WRAP_ALG)?;
+    let mut wrapped = crypto::mac(consts::KEY_WRAP_NONCE_HASH, kek, key, policy)?;
+    wrapped.truncate(enc.nonce_len());
+    let ct = enc.process(kek, &wrapped, &[], key, policy)?;
+    wrapped.extend(ct);
+    Ok(wrapped)
+}
+
+// the data key, if the key-encryption key is the right one
+
+pub fn unwrap_key(
+    kek: &[u8],
+    wrapped: &[u8],
+    policy: &Box<dyn crypto::CryptoPolicy>,
+) -> Option<Vec<u8>> {
+    let dec = decryption(consts::KEY_WRAP_ALG).ok()?;
+    if wrapped.len() < dec.nonce_len() {
+        return None;
+    }
+    let (iv, ct) = wrapped.split_at(dec.nonce_len());
+    dec.process(kek, iv, &[], ct, policy).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
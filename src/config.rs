@@ -0,0 +1,421 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+use toml::value::{Table, Value};
+
+use consts;
+use error::EnprotError;
+use etree::ParseOps;
+use recipient;
+use syntax;
+
+// separator settings of one configuration layer
+
+#[derive(Default)]
+pub struct Separators {
+    pub syntax: Option<String>,
+    pub left_sep: Option<String>,
+    pub right_sep: Option<String>,
+}
+
+impl Separators {
+    // apply on top of the separators already in paops, for the file fname
+    pub fn apply(&self, paops: &mut ParseOps, fname: &str) {
+        // explicit separators override those of the preset, even with "auto"
+        let preset = match self.syntax.as_deref() {
+            Some("auto") => syntax::by_path(fname),
+            Some(name) => syntax::by_name(name),
+            None => None,
+        };
+        if let Some(syntax) = preset {
+            paops.left_sep = syntax.left_sep.to_string();
+            paops.right_sep = syntax.right_sep.to_string();
+        }
+        if let Some(ref left_sep) = self.left_sep {
+            paops.left_sep = left_sep.clone();
+        }
+        if let Some(ref right_sep) = self.right_sep {
+            paops.right_sep = right_sep.clone();
+        }
+    }
+}
+
+// settings read from a .enprot.toml file
+
+#[derive(Default)]
+pub struct Config {
+    pub path: PathBuf,
+    pub casdir: Option<Vec<PathBuf>>,
+    pub write_casdir: Option<PathBuf>,
+    pub cas_layout: Option<String>,
+    pub cas_hash: Option<String>,
+    pub cas_key_file: Option<PathBuf>,
+    pub cas: Option<String>, // backend URL, relative to the configuration
+    pub policy: Option<String>,
+    pub defaults: Option<String>,
+    pub max_depth: Option<usize>,
+    pub verbose: Option<bool>,
+    pub separators: Separators,
+    pub store: Option<Vec<String>>,
+    pub fetch: Option<Vec<String>>,
+    pub encrypt: Option<Vec<String>>,
+    pub encrypt_store: Option<Vec<String>>,
+    pub decrypt: Option<Vec<String>>,
+    pub passwords: BTreeMap<String, String>,
+    pub document_id: Option<String>,
+    pub recipients: Option<Vec<Vec<u8>>>,
+    pub pbkdf: Option<String>,
+    pub pbkdf_msec: Option<u32>,
+    pub pbkdf_salt_len: Option<usize>,
+    pub pbkdf_params: Option<BTreeMap<String, usize>>,
+    pub pbkdf_salt: Option<Vec<u8>>,
+    pub pbkdf_disable_cache: Option<bool>,
+    pub cipher: Option<String>,
+    pub cipher_iv: Option<Vec<u8>>,
+    overrides: Vec<(Pattern, Separators)>, // per-glob separators
+}
+
+impl Config {
+    // find the closest configuration file in dir or any of its parents, up
+    // to the root of the enclosing repository or the home directory; outside
+    // of both only dir itself is searched
+    pub fn discover(dir: &Path, trusted: bool) -> Result<Option<Config>, EnprotError> {
+        let home = env::var_os("HOME").map(|home| fs::canonicalize(&home).unwrap_or(home.into()));
+        let is_root = |dir: &Path| {
+            home.as_deref() == Some(dir)
+                || consts::VCS_DIRS.iter().any(|vcs| dir.join(vcs).exists())
+        };
+        let bounded = dir.ancestors().any(&is_root);
+        for dir in dir.ancestors() {
+            let path = dir.join(consts::CONFIG_FILE);
+            if path.is_file() {
+                return Config::load(&path, trusted).map(Some);
+            }
+            if !bounded || is_root(dir) {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    // settings sending data elsewhere are only taken from a trusted file
+    pub fn load(path: &Path, trusted: bool) -> Result<Config, EnprotError> {
+        let config_err = |msg: String| EnprotError::Config {
+            path: path.display().to_string(),
+            msg,
+        };
+        let text = fs::read_to_string(path).map_err(|io| EnprotError::Io {
+            path: path.display().to_string(),
+            io,
+        })?;
+        let table = match text.parse::<Value>() {
+            Ok(Value::Table(table)) => table,
+            Ok(_) => return Err(config_err("expected a table".to_string())),
+            Err(e) => return Err(config_err(e.to_string())),
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Config::from_table(path, dir, table, trusted).map_err(config_err)
+    }
+
+    fn from_table(
+        path: &Path,
+        dir: &Path,
+        mut table: Table,
+        trusted: bool,
+    ) -> Result<Config, String> {
+        let mut config = Config {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+
+        // relative to the directory holding the configuration
+        let casdir = |casdir: PathBuf| {
+            let casdir = dir.join(casdir);
+            if casdir.is_dir() {
+                Ok(casdir)
+            } else {
+                Err(format!("casdir {} must be a directory", casdir.display()))
+            }
+        };
+        // a search path, as a list or separated like $PATH
+        let casdirs = match table.get("casdir") {
+            Some(Value::Array(_)) => take_list(&mut table, "casdir")?,
+            _ => take_str(&mut table, "casdir")?.map(|dirs| {
+                env::split_paths(&dirs)
+                    .map(|dir| dir.to_string_lossy().into_owned())
+                    .collect()
+            }),
+        };
+        if let Some(casdirs) = casdirs {
+            config.casdir = Some(
+                casdirs
+                    .into_iter()
+                    .map(|dir| casdir(PathBuf::from(dir)))
+                    .collect::<Result<Vec<PathBuf>, String>>()?,
+            );
+        }
+        if let Some(dir) = take_str(&mut table, "write-casdir")? {
+            config.write_casdir = Some(casdir(PathBuf::from(dir))?);
+        }
+        let untrusted = |key: &str| format!("'{}' is only accepted with --trust-config", key);
+        config.cas = take_str(&mut table, "cas")?;
+        // a file: or sqlite: path may lie outside of the project as well
+        if !trusted && config.cas.is_some() {
+            return Err(untrusted("cas"));
+        }
+        if config.cas.is_some() && (config.casdir.is_some() || config.write_casdir.is_some()) {
+            return Err("'cas' can't be combined with 'casdir' or 'write-casdir'".to_string());
+        }
+        config.cas_layout = take_choice(&mut table, "cas-layout", consts::VALID_CAS_LAYOUTS)?;
+        config.cas_hash = take_choice(&mut table, "cas-hash", consts::VALID_CAS_HASH_ALGS)?;
+        config.cas_key_file = take_str(&mut table, "cas-key-file")?.map(|path| dir.join(path));
+        if !trusted && config.cas_key_file.is_some() {
+            return Err(untrusted("cas-key-file"));
+        }
+        config.policy = take_choice(&mut table, "policy", consts::VALID_POLICIES)?;
+        config.defaults = take_choice(&mut table, "defaults", consts::VALID_POLICIES)?;
+        config.max_depth = take_uint(&mut table, "max-depth")?.map(|n| n as usize);
+        config.verbose = take_bool(&mut table, "verbose")?;
+        config.separators = take_separators(&mut table)?;
+        config.store = take_list(&mut table, "store")?;
+        config.fetch = take_list(&mut table, "fetch")?;
+        config.encrypt = take_list(&mut table, "encrypt")?;
+        config.encrypt_store = take_list(&mut table, "encrypt-store")?;
+        config.decrypt = take_list(&mut table, "decrypt")?;
+        if let Some(mut keys) = take_table(&mut table, "keys")? {
+            if !owner_only(path) {
+                return Err(
+                    "'keys' requires the file to be readable by its owner only (mode 0600)"
+                        .to_string(),
+                );
+            }
+            let words: Vec<String> = keys.keys().cloned().collect();
+            for word in words {
+                let password = take_str(&mut keys, &word)?.unwrap();
+                config.passwords.insert(word, password);
+            }
+        }
+
+        config.document_id = take_str(&mut table, "document-id")?;
+        if let Some(keys) = take_list(&mut table, "recipients")? {
+            if !trusted {
+                return Err(untrusted("recipients"));
+            }
+            config.recipients = Some(
+                keys.iter()
+                    .map(|key| recipient::parse_public(key).map_err(|e| e.to_string()))
+                    .collect::<Result<Vec<Vec<u8>>, String>>()?,
+            );
+        }
+
+        if let Some(mut pbkdf) = take_table(&mut table, "pbkdf")? {
+            config.pbkdf = take_choice(&mut pbkdf, "alg", consts::VALID_PBKDF_ALGS)?;
+            config.pbkdf_msec = take_positive(&mut pbkdf, "msec")?.map(|n| n as u32);
+            config.pbkdf_salt_len = take_positive(&mut pbkdf, "salt-len")?.map(|n| n as usize);
+            if let Some(mut params) = take_table(&mut pbkdf, "params")? {
+                let mut values = BTreeMap::new();
+                let names: Vec<String> = params.keys().cloned().collect();
+                for name in names {
+                    let value = take_uint(&mut params, &name)?.unwrap();
+                    values.insert(name, value as usize);
+                }
+                config.pbkdf_params = Some(values);
+            }
+            // a fixed salt or IV is for tests, and weakens anything encrypted with it
+            config.pbkdf_salt = take_hex(&mut pbkdf, "salt")?;
+            if !trusted && config.pbkdf_salt.is_some() {
+                return Err(untrusted("pbkdf.salt"));
+            }
+            config.pbkdf_disable_cache = take_bool(&mut pbkdf, "disable-cache")?;
+            check_empty(&pbkdf, "pbkdf.")?;
+        }
+
+        if let Some(mut cipher) = take_table(&mut table, "cipher")? {
+            config.cipher = take_choice(&mut cipher, "alg", consts::VALID_CIPHER_ALGS)?;
+            config.cipher_iv = take_hex(&mut cipher, "iv")?;
+            if !trusted && config.cipher_iv.is_some() {
+                return Err(untrusted("cipher.iv"));
+            }
+            check_empty(&cipher, "cipher.")?;
+        }
+
+        if let Some(overrides) = table.remove("separators") {
+            let overrides = match overrides {
+                Value::Array(overrides) => overrides,
+                _ => return Err("separators must be an array of tables".to_string()),
+            };
+            for entry in overrides {
+                let mut entry = match entry {
+                    Value::Table(entry) => entry,
+                    _ => return Err("separators must be an array of tables".to_string()),
+                };
+                let glob = match take_str(&mut entry, "glob")? {
+                    Some(glob) => glob,
+                    None => return Err("separators entry without glob".to_string()),
+                };
+                let pattern =
+                    Pattern::new(&glob).map_err(|e| format!("invalid glob '{}': {}", glob, e))?;
+                let separators = take_separators(&mut entry)?;
+                check_empty(&entry, "separators.")?;
+                config.overrides.push((pattern, separators));
+            }
+        }
+
+        check_empty(&table, "")?;
+        Ok(config)
+    }
+
+    // the per-glob separators matching a file, if any
+    pub fn separators_for(&self, file: &Path) -> Option<&Separators> {
+        let dir = self.path.parent()?;
+        let relative = file.strip_prefix(dir).ok()?;
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.overrides
+            .iter()
+            .find(|(pattern, _)| {
+                // patterns without a slash apply to the file name alone
+                if pattern.as_str().contains('/') {
+                    pattern.matches_path_with(relative, options)
+                } else {
+                    relative
+                        .file_name()
+                        .is_some_and(|name| pattern.matches_path_with(Path::new(name), options))
+                }
+            })
+            .map(|(_, separators)| separators)
+    }
+}
+
+#[cfg(unix)]
+fn owner_only(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match fs::metadata(path) {
+        Ok(meta) => meta.permissions().mode() & 0o077 == 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn owner_only(_path: &Path) -> bool {
+    true
+}
+
+fn take_separators(table: &mut Table) -> Result<Separators, String> {
+    Ok(Separators {
+        syntax: take_choice(table, "syntax", consts::VALID_SYNTAXES)?,
+        left_sep: take_str(table, "left-separator")?,
+        right_sep: take_str(table, "right-separator")?,
+    })
+}
+
+fn check_empty(table: &Table, prefix: &str) -> Result<(), String> {
+    match table.keys().next() {
+        Some(key) => Err(format!("unknown key '{}{}'", prefix, key)),
+        None => Ok(()),
+    }
+}
+
+fn take_str(table: &mut Table, key: &str) -> Result<Option<String>, String> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(format!("'{}' must be a string", key)),
+    }
+}
+
+fn take_bool(table: &mut Table, key: &str) -> Result<Option<bool>, String> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(Value::Boolean(b)) => Ok(Some(b)),
+        Some(_) => Err(format!("'{}' must be a boolean", key)),
+    }
+}
+
+fn take_uint(table: &mut Table, key: &str) -> Result<Option<u64>, String> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(Value::Integer(n)) if n >= 0 && n <= u32::MAX as i64 => Ok(Some(n as u64)),
+        Some(_) => Err(format!("'{}' must be a number 0 or greater", key)),
+    }
+}
+
+fn take_positive(table: &mut Table, key: &str) -> Result<Option<u64>, String> {
+    match take_uint(table, key)? {
+        Some(0) => Err(format!("'{}' must be a number > 0", key)),
+        n => Ok(n),
+    }
+}
+
+fn take_choice(table: &mut Table, key: &str, valid: &[&str]) -> Result<Option<String>, String> {
+    match take_str(table, key)? {
+        Some(ref value) if !valid.contains(&value.as_str()) => Err(format!(
+            "'{}' isn't a valid value for '{}' [possible values: {}]",
+            value,
+            key,
+            valid.join(", ")
+        )),
+        value => Ok(value),
+    }
+}
+
+fn take_list(table: &mut Table, key: &str) -> Result<Option<Vec<String>>, String> {
+    let values = match table.remove(key) {
+        None => return Ok(None),
+        Some(Value::Array(values)) => values,
+        Some(_) => return Err(format!("'{}' must be an array of strings", key)),
+    };
+    values
+        .into_iter()
+        .map(|value| match value {
+            Value::String(s) => Ok(s),
+            _ => Err(format!("'{}' must be an array of strings", key)),
+        })
+        .collect::<Result<Vec<String>, String>>()
+        .map(Some)
+}
+
+fn take_table(table: &mut Table, key: &str) -> Result<Option<Table>, String> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(Value::Table(t)) => Ok(Some(t)),
+        Some(_) => Err(format!("'{}' must be a table", key)),
+    }
+}
+
+fn take_hex(table: &mut Table, key: &str) -> Result<Option<Vec<u8>>, String> {
+    match take_str(table, key)? {
+        None => Ok(None),
+        Some(value) => hex::decode(&value)
+            .map(Some)
+            .map_err(|_| format!("'{}' must be a hex string", key)),
+    }
+}
@@ -25,9 +25,51 @@ pub const VALID_CIPHER_ALGS: &[&str] = &["aes-256-siv", "aes-256-gcm", "aes-256-
 
 pub const DEFAULT_MAX_DEPTH: usize = 100;
 
+// appended to the names of backups made with --backup
+pub const DEFAULT_BACKUP_SUFFIX: &str = "~";
+
+// exit status of --dry-run when some file would change
+pub const EXIT_DRY_RUN_CHANGED: i32 = 2;
+
+// project configuration file, looked up from each input file upwards
+pub const CONFIG_FILE: &str = ".enprot.toml";
+
+// directories marking the root of a repository, where the lookup stops
+pub const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
+// format version of new ENCRYPTED segments, recorded in the version extfield
+pub const FORMAT_VERSION: &str = "2";
+
+// public-key recipients
+pub const RECIPIENT_KEY_ALG: &str = "x25519";
+pub const RECIPIENT_KDF: &str = "HKDF(SHA-256)";
+pub const KEK_LEN: usize = 32;
+pub const KEY_WRAP_ALG: &str = "aes-256-gcm";
+pub const KEY_WRAP_NONCE_HASH: &str = "sha3-256";
+
+// password stanza prefix of a data key wrapped under the legacy KDF
+pub const LEGACY_STANZA: &str = "$legacy$$";
+
+// content addressed storage
+pub const CAS_MARKER: &str = ".enprot-cas";
+pub const DEFAULT_CAS_LAYOUT: &str = "flat";
+pub const VALID_CAS_LAYOUTS: &[&str] = &["flat", "sharded"];
+pub const DEFAULT_CAS_HASH_ALG: &str = "sha3-256";
+pub const VALID_CAS_HASH_ALGS: &[&str] = &["sha3-256", "sha3-512", "sha256", "sha512", "blake2b"];
+pub const CAS_LOST_FOUND: &str = "lost+found";
+// hex digits of an object name that make up its directory in the sharded layout
+pub const CAS_SHARD_LEN: usize = 2;
+pub const CAS_KEY_MIN_LEN: usize = 16;
+
+// bundles of CAS objects
+pub const BUNDLE_MANIFEST: &str = "MANIFEST.toml";
+pub const BUNDLE_OBJECTS: &str = "objects/";
+pub const BUNDLE_VERSION: i64 = 1;
+
 // parsing separators
 pub const DEFAULT_LEFT_SEP: &str = "// <(";
 pub const DEFAULT_RIGHT_SEP: &str = ")>";
+pub const VALID_SYNTAXES: &[&str] = &["c", "hash", "html", "tex", "raw", "auto"];
 
 // valid value lists
 pub const VALID_PBKDF_ALGS: &[&str] = &[
@@ -0,0 +1,410 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	public API for EPT documents
+
+use std::io;
+use std::io::{BufRead, Write};
+
+use cas;
+use error::{EnprotError, ParseErrorKind};
+use etree;
+use etree::{ParseOps, TextNode, TextTree};
+use prot;
+
+/// A parsed EPT document.
+///
+/// The document owns its `TextNode` tree, which can be inspected through
+/// `nodes()`. All processing parameters (separators, keywords, passwords,
+/// CAS directory, crypto policy) are carried by the `ParseOps` passed to
+/// each method.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Document {
+    nodes: TextTree,
+}
+
+impl Document {
+    /// Parses a document from `reader`.
+    pub fn parse<R: BufRead>(reader: R, paops: &mut ParseOps) -> Result<Document, EnprotError> {
+        Ok(Document {
+            nodes: etree::parse(reader, paops)?,
+        })
+    }
+
+    /// Applies the operations in `paops` (store, fetch, encrypt, decrypt)
+    /// and returns the resulting document.
+    pub fn transform(&self, paops: &mut ParseOps) -> Result<Document, EnprotError> {
+        Ok(Document {
+            nodes: etree::transform(&self.nodes, paops)?,
+        })
+    }
+
+    /// Writes the document to `writer`.
+    pub fn write<W: Write>(&self, writer: &mut W, paops: &mut ParseOps) -> io::Result<()> {
+        etree::tree_write(writer, &self.nodes, paops)
+    }
+
+    /// The top-level nodes of the document.
+    pub fn nodes(&self) -> &[TextNode] {
+        &self.nodes
+    }
+
+    /// Consumes the document, returning its tree.
+    pub fn into_nodes(self) -> TextTree {
+        self.nodes
+    }
+}
+
+/// How the contents of a segment are kept.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SegmentState {
+    /// A BEGIN-END block in the clear.
+    Plain,
+    /// An ENCRYPTED block with DATA lines.
+    EncryptedInline,
+    /// An ENCRYPTED line referring to a ciphertext in CAS.
+    EncryptedStored,
+    /// A STORED line referring to plain contents in CAS.
+    Stored,
+}
+
+impl SegmentState {
+    /// The name used in listings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SegmentState::Plain => "plain",
+            SegmentState::EncryptedInline => "encrypted-inline",
+            SegmentState::EncryptedStored => "encrypted-cas",
+            SegmentState::Stored => "stored",
+        }
+    }
+}
+
+/// A summary of one segment of a parsed document.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub keyword: String,
+    pub state: SegmentState,
+    /// First and last source line, including the directives.
+    pub lines: (usize, usize),
+    /// The CAS identifier, for stored segments.
+    pub cas: Option<String>,
+    /// The size of the ciphertext or stored contents, if available.
+    pub size: Option<usize>,
+    /// The PBKDF algorithm and its parameters, for encrypted segments.
+    pub pbkdf: Option<(String, String)>,
+    /// The cipher algorithm, for encrypted segments.
+    pub cipher: Option<String>,
+    /// Segments nested in a plain segment.
+    pub children: Vec<Segment>,
+}
+
+impl Document {
+    /// Lists the segments of a document as parsed, without modifying it.
+    ///
+    /// The sizes of stored segments are looked up in the CAS in `paops`,
+    /// without reading the objects; they are left out if an object is
+    /// missing.
+    pub fn segments(&self, paops: &ParseOps) -> Vec<Segment> {
+        segments(&self.nodes, paops)
+    }
+}
+
+fn segments(nodes: &[TextNode], paops: &ParseOps) -> Vec<Segment> {
+    let mut list = Vec::new();
+    for node in nodes {
+        let segment = match node {
+            TextNode::BeginEnd {
+                keyw,
+                txt,
+                begin,
+                end,
+            } => Segment {
+                keyword: keyw.clone(),
+                state: SegmentState::Plain,
+                lines: (begin.lineno, end.last_lineno()),
+                cas: None,
+                size: None,
+                pbkdf: None,
+                cipher: None,
+                children: segments(txt, paops),
+            },
+            TextNode::Encrypted {
+                keyw,
+                txt,
+                extfields,
+                begin,
+                end,
+            } => {
+                let (state, cas, size) = match txt.first() {
+                    Some(TextNode::Stored { cas: hexhash, .. }) => (
+                        SegmentState::EncryptedStored,
+                        Some(hexhash.clone()),
+                        cas::size(hexhash, paops).ok().map(|size| size as usize),
+                    ),
+                    _ => {
+                        let size = txt
+                            .iter()
+                            .map(|node| match node {
+                                TextNode::Data(data, _) => data.len(),
+                                _ => 0,
+                            })
+                            .sum();
+                        (SegmentState::EncryptedInline, None, Some(size))
+                    }
+                };
+                // "$alg$params$salt", or that of the first password stanza,
+                // with the legacy KDF when absent
+                let pbkdf = extfields.get("pbkdf").or_else(|| {
+                    extfields
+                        .iter()
+                        .find(|(key, _)| key.starts_with("password."))
+                        .map(|(_, stanza)| stanza)
+                });
+                let pbkdf = match pbkdf {
+                    Some(pbkdf) => {
+                        let fields: Vec<&str> = pbkdf.split('$').collect();
+                        (
+                            fields.get(1).unwrap_or(&"").to_string(),
+                            fields.get(2).unwrap_or(&"").to_string(),
+                        )
+                    }
+                    None => ("legacy".to_string(), String::new()),
+                };
+                let cipher = match extfields.get("cipher") {
+                    Some(cipher) => cipher.split('$').next().unwrap_or("").to_string(),
+                    None => "aes-256-siv".to_string(),
+                };
+                Segment {
+                    keyword: keyw.clone(),
+                    state,
+                    lines: (begin.lineno, end.last_lineno()),
+                    cas,
+                    size,
+                    pbkdf: Some(pbkdf),
+                    cipher: Some(cipher),
+                    children: Vec::new(),
+                }
+            }
+            TextNode::Stored { keyw, cas, line } => Segment {
+                keyword: keyw.clone(),
+                state: SegmentState::Stored,
+                lines: (line.lineno, line.last_lineno()),
+                cas: Some(cas.clone()),
+                size: cas::size(cas, paops).ok().map(|size| size as usize),
+                pbkdf: None,
+                cipher: None,
+                children: Vec::new(),
+            },
+            TextNode::Plain(_) | TextNode::Data(..) => continue,
+        };
+        list.push(segment);
+    }
+    list
+}
+
+/// The outcome of checking one segment.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CheckStatus {
+    /// Everything that could be checked is fine.
+    Ok,
+    /// The ciphertext was decrypted with the supplied password.
+    Authenticated,
+    /// The ciphertext wasn't authenticated, as no password was supplied.
+    NoKey,
+    /// The check failed, with the reason.
+    Failed(String),
+}
+
+/// The result of verifying one segment of a document.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Check {
+    pub keyword: String,
+    pub state: SegmentState,
+    /// First and last source line, including the directives.
+    pub lines: (usize, usize),
+    /// The CAS identifier, for stored segments.
+    pub cas: Option<String>,
+    pub status: CheckStatus,
+    /// Checks of nested segments, including those found in stored or
+    /// decrypted contents.
+    pub children: Vec<Check>,
+}
+
+impl Check {
+    /// Whether this segment and all segments nested in it passed.
+    pub fn passed(&self) -> bool {
+        match self.status {
+            CheckStatus::Failed(_) => false,
+            _ => self.children.iter().all(Check::passed),
+        }
+    }
+}
+
+impl Document {
+    /// Verifies the segments of a document without modifying it.
+    ///
+    /// Every CAS reference must resolve in the CAS in `paops` and match its
+    /// hash. Encrypted segments are authenticated when `paops` has a
+    /// password for them; there is no prompting. Stored and decrypted
+    /// contents are checked in turn, down to `paops.max_depth`.
+    pub fn verify(&self, paops: &mut ParseOps) -> Vec<Check> {
+        verify(&self.nodes, paops, 0)
+    }
+}
+
+fn verify(nodes: &[TextNode], paops: &mut ParseOps, depth: usize) -> Vec<Check> {
+    let mut list = Vec::new();
+    for node in nodes {
+        let check = match node {
+            TextNode::BeginEnd {
+                keyw,
+                txt,
+                begin,
+                end,
+            } => Check {
+                keyword: keyw.clone(),
+                state: SegmentState::Plain,
+                lines: (begin.lineno, end.last_lineno()),
+                cas: None,
+                status: CheckStatus::Ok,
+                children: verify(txt, paops, depth),
+            },
+            TextNode::Encrypted {
+                keyw,
+                txt,
+                extfields,
+                begin,
+                end,
+            } => {
+                let (state, cas, ct) = match txt.first() {
+                    Some(TextNode::Stored { cas: hexhash, .. }) => (
+                        SegmentState::EncryptedStored,
+                        Some(hexhash.clone()),
+                        cas::load(hexhash, paops),
+                    ),
+                    Some(TextNode::Data(data, _)) => {
+                        (SegmentState::EncryptedInline, None, Ok(data.to_vec()))
+                    }
+                    _ => (
+                        SegmentState::EncryptedInline,
+                        None,
+                        Err(EnprotError::NoEncryptedData(keyw.clone())),
+                    ),
+                };
+                let mut check = Check {
+                    keyword: keyw.clone(),
+                    state,
+                    lines: (begin.lineno, end.last_lineno()),
+                    cas,
+                    status: CheckStatus::NoKey,
+                    children: Vec::new(),
+                };
+                let ct = match ct {
+                    Ok(ct) => ct,
+                    Err(e) => {
+                        check.status = CheckStatus::Failed(e.to_string());
+                        list.push(check);
+                        continue;
+                    }
+                };
+                // a matching identity, else the password if there is one
+                let mut pt = prot::decrypt_for_identities(
+                    keyw,
+                    &ct,
+                    &paops.identities,
+                    extfields,
+                    &paops.document_id,
+                    &paops.policy,
+                )
+                .transpose();
+                if pt.is_none() {
+                    if let Some(pass) = paops.passwords.get(keyw).cloned() {
+                        pt = Some(prot::decrypt(
+                            keyw,
+                            ct,
+                            &pass,
+                            extfields,
+                            &paops.document_id,
+                            &mut paops.pbkdf_cache,
+                            &paops.policy,
+                        ));
+                    }
+                }
+                match pt {
+                    Some(Ok((pt, _))) => {
+                        check.status = CheckStatus::Authenticated;
+                        verify_contents(&mut check, pt, "decrypted", paops, depth);
+                    }
+                    Some(Err(e)) => check.status = CheckStatus::Failed(e.to_string()),
+                    None => (),
+                }
+                check
+            }
+            TextNode::Stored { keyw, cas, line } => {
+                let mut check = Check {
+                    keyword: keyw.clone(),
+                    state: SegmentState::Stored,
+                    lines: (line.lineno, line.last_lineno()),
+                    cas: Some(cas.clone()),
+                    status: CheckStatus::Ok,
+                    children: Vec::new(),
+                };
+                match cas::load(cas, paops) {
+                    Ok(data) => verify_contents(&mut check, data, cas, paops, depth),
+                    Err(e) => check.status = CheckStatus::Failed(e.to_string()),
+                }
+                check
+            }
+            TextNode::Plain(_) | TextNode::Data(..) => continue,
+        };
+        list.push(check);
+    }
+    list
+}
+
+// check the segments inside stored or decrypted contents
+fn verify_contents(
+    check: &mut Check,
+    data: Vec<u8>,
+    path: &str,
+    paops: &mut ParseOps,
+    depth: usize,
+) {
+    if paops.max_depth != 0 && depth >= paops.max_depth {
+        check.status = CheckStatus::Failed(ParseErrorKind::MaxDepth(paops.max_depth).to_string());
+        return;
+    }
+    let fname = paops.fname.clone();
+    match etree::blob_to_tree(data, path.to_string(), paops) {
+        Ok(tree) => check.children = verify(&tree, paops, depth + 1),
+        Err(e) => check.status = CheckStatus::Failed(e.to_string()),
+    }
+    paops.fname = fname;
+}
+
+impl From<TextTree> for Document {
+    fn from(nodes: TextTree) -> Document {
+        Document { nodes }
+    }
+}
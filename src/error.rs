@@ -0,0 +1,204 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// what went wrong while parsing a directive
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    RightSeparatorMissing(String),
+    UnknownSection(String),
+    Base64(String),
+    BeginArgs,
+    DuplicateExtField(String),
+    EncryptedArgs(usize),
+    InvalidCasId(String),
+    EndPadding,
+    EndMismatch(String),
+    EndWithoutStart,
+    EncryptedContents { keyw: String, count: usize },
+    EncryptedNotData(String),
+    StoredArgs,
+    Unterminated { section: &'static str, keyw: String },
+    EmptyDirective,
+    MaxDepth(usize),
+    InvalidUtf8,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::RightSeparatorMissing(sep) => {
+                write!(f, "Right separator '{}' missing", sep)
+            }
+            ParseErrorKind::UnknownSection(name) => write!(f, "Unknown section '{}'", name),
+            ParseErrorKind::Base64(token) => write!(f, "Error decoding base64 '{}'", token),
+            ParseErrorKind::BeginArgs => write!(f, "BEGIN needs a single keyword"),
+            ParseErrorKind::DuplicateExtField(key) => {
+                write!(f, "Duplicate extended field '{}'", key)
+            }
+            ParseErrorKind::EncryptedArgs(count) => {
+                write!(f, "ENCRYPTED has wrong number of parameters ({})", count)
+            }
+            ParseErrorKind::InvalidCasId(id) => write!(f, "Invalid CAS identifier '{}'", id),
+            ParseErrorKind::EndPadding => write!(f, "Unknown padding in END"),
+            ParseErrorKind::EndMismatch(keyw) => write!(f, "END mismatch (expected '{}')", keyw),
+            ParseErrorKind::EndWithoutStart => write!(f, "END without a start clause"),
+            ParseErrorKind::EncryptedContents { keyw, count } => write!(
+                f,
+                "{} elements in encrypted {} (must be a single DATA or STORED)",
+                count, keyw
+            ),
+            ParseErrorKind::EncryptedNotData(keyw) => {
+                write!(f, "Not DATA or STORED element in encrypted {}", keyw)
+            }
+            ParseErrorKind::StoredArgs => write!(f, "STORED needs two parameters"),
+            ParseErrorKind::Unterminated { section, keyw } => {
+                write!(f, "{} {} without END", section, keyw)
+            }
+            ParseErrorKind::EmptyDirective => write!(f, "Empty directive"),
+            ParseErrorKind::InvalidUtf8 => write!(f, "Invalid UTF-8 in directive"),
+            ParseErrorKind::MaxDepth(depth) => {
+                write!(f, "Maximum nesting depth ({}) exceeded", depth)
+            }
+        }
+    }
+}
+
+// the error type used throughout enprot
+
+#[derive(Debug)]
+pub enum EnprotError {
+    Parse {
+        file: String,
+        line: usize,
+        col: usize,
+        kind: ParseErrorKind,
+    },
+    Io {
+        path: String,
+        io: io::Error,
+    },
+    Cas {
+        hash: String,
+        io: io::Error,
+    },
+    CasInvalidId(String),
+    CasUrl {
+        url: String,
+        msg: String,
+    },
+    NoEncryptedData(String),
+    CasMismatch {
+        hash: String,
+        actual: String,
+    },
+    Decrypt {
+        keyword: String,
+        source: Box<EnprotError>,
+    },
+    Policy {
+        rule: String,
+    },
+    InvalidAlgorithm {
+        kind: &'static str,
+        alg: String,
+    },
+    Prompt(io::Error),
+    Crypto(&'static str),
+    Config {
+        path: String,
+        msg: String,
+    },
+    Bundle {
+        path: String,
+        msg: String,
+    },
+    Identity {
+        path: String,
+        msg: &'static str,
+    },
+}
+
+impl fmt::Display for EnprotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnprotError::Parse {
+                file,
+                line,
+                col,
+                kind,
+            } => write!(f, "Parse error: {} at {}:{}:{}", kind, file, line, col),
+            EnprotError::Io { path, io } => write!(f, "I/O error on {}: {}", path, io),
+            EnprotError::Cas { hash, io } => write!(f, "CAS error on {}: {}", hash, io),
+            EnprotError::CasInvalidId(hash) => write!(f, "Not a valid CAS identifier: {}", hash),
+            EnprotError::CasUrl { url, msg } => write!(f, "Invalid CAS URL {}: {}", url, msg),
+            EnprotError::NoEncryptedData(keyw) => write!(f, "No data in ENCRYPTED {}", keyw),
+            EnprotError::CasMismatch { hash, actual } => write!(
+                f,
+                "CAS content hash mismatch (expected {}, found {})",
+                hash, actual
+            ),
+            EnprotError::Decrypt { keyword, source } => {
+                write!(f, "Error decrypting {}: {}", keyword, source)
+            }
+            EnprotError::Policy { rule } => write!(f, "{}", rule),
+            EnprotError::InvalidAlgorithm { kind, alg } => {
+                write!(f, "Invalid {} algorithm: '{}'", kind, alg)
+            }
+            EnprotError::Prompt(io) => write!(f, "Failed to read password: {}", io),
+            EnprotError::Crypto(msg) => write!(f, "{}", msg),
+            EnprotError::Config { path, msg } => {
+                write!(f, "Invalid configuration in {}: {}", path, msg)
+            }
+            EnprotError::Bundle { path, msg } => write!(f, "Invalid bundle {}: {}", path, msg),
+            EnprotError::Identity { path, msg } => {
+                write!(f, "Invalid identity file {}: {}", path, msg)
+            }
+        }
+    }
+}
+
+// the messages include their cause as well, for callers that only print them
+impl Error for EnprotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EnprotError::Io { io, .. } | EnprotError::Cas { io, .. } | EnprotError::Prompt(io) => {
+                Some(io)
+            }
+            EnprotError::Decrypt { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl EnprotError {
+    pub fn policy(rule: &str) -> EnprotError {
+        EnprotError::Policy {
+            rule: rule.to_string(),
+        }
+    }
+}
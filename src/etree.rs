@@ -24,13 +24,17 @@
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::io;
 use std::io::prelude::*;
 use std::io::{Cursor, Write};
 use std::path::{Path, PathBuf};
+use std::str;
 
 use cas;
+use cas::CasBackend;
 use consts;
 use crypto::CryptoPolicy;
+use error::{EnprotError, ParseErrorKind};
 use pbkdf::PBKDFCache;
 use prot;
 use utils;
@@ -58,6 +62,7 @@ impl PBKDFOptions {
 pub struct CipherOptions {
     pub alg: String,
     pub iv: Option<Vec<u8>>,
+    pub key: Option<Vec<u8>>, // data key (randomly generated if None)
 }
 
 impl CipherOptions {
@@ -65,6 +70,7 @@ impl CipherOptions {
         CipherOptions {
             alg: policy.default_cipher_alg(),
             iv: None,
+            key: None,
         }
     }
 }
@@ -79,10 +85,23 @@ pub struct ParseOps {
     pub fetch: HashSet<String>,                    // keywords to fetch
     pub encrypt: HashSet<String>,                  // keywords to encrypt
     pub decrypt: HashSet<String>,                  // keywords to decrypt
+    pub rekey: HashSet<String>,                    // keywords to re-encrypt
+    pub access_add: HashSet<String>,               // keywords to give a password
+    pub access_remove: HashSet<String>,            // keywords to take a password from
     pub passwords: HashMap<String, String>,        // passwords
+    pub new_passwords: HashMap<String, String>,    // passwords to re-encrypt with
     pub fname: String,                             // file name being parsed
     pub casdir: PathBuf,                           // directory for cas objects
+    pub cas_layout: String,                        // layout of a new casdir
+    pub cas: Option<Box<dyn CasBackend>>,          // cas backend, if not casdir
+    pub cas_key: Option<Vec<u8>>,                  // key for naming cas objects
+    pub cas_hash: String,                          // hash naming new cas objects
+    pub document_id: Option<String>,               // document id bound to ciphertexts
+    pub recipients: Vec<Vec<u8>>,                  // public keys to encrypt to
+    pub identities: Vec<Vec<u8>>,                  // secret keys to decrypt with
     pub verbose: bool,                             // verbose output to stdout
+    pub notes: Vec<String>,                        // verbose notes for the caller to print
+    pub dry_run: bool,                             // don't write cas objects
     pub rng: Option<botan::RandomNumberGenerator>, // RNG to use
     pub policy: Box<dyn CryptoPolicy>,             // the crypto alg policy
     pub pbkdfopts: PBKDFOptions,                   // the PBKDF options
@@ -101,11 +120,24 @@ impl ParseOps {
             fetch: HashSet::new(),
             encrypt: HashSet::new(),
             decrypt: HashSet::new(),
+            rekey: HashSet::new(),
+            access_add: HashSet::new(),
+            access_remove: HashSet::new(),
             passwords: HashMap::new(),
+            new_passwords: HashMap::new(),
             fname: "".to_string(),
             casdir: Path::new("").to_path_buf(),
+            cas_layout: consts::DEFAULT_CAS_LAYOUT.to_string(),
+            cas: None,
+            cas_key: None,
+            cas_hash: policy.default_cas_hash_alg(),
+            document_id: None,
+            recipients: Vec::new(),
+            identities: Vec::new(),
             level: 0,
             verbose: false,
+            notes: Vec::new(),
+            dry_run: false,
             rng: Some(botan::RandomNumberGenerator::new().unwrap()),
             pbkdfopts: PBKDFOptions::new(&policy),
             pbkdf_cache: Some(Vec::new()),
@@ -116,109 +148,181 @@ impl ParseOps {
 }
 
 const DATA_BYTES_PER_LINE: usize = 48; // that's 64 characters
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+// split a raw line into its content and its terminator
+fn split_eol(raw: &[u8]) -> (&[u8], &str) {
+    if raw.ends_with(b"\r\n") {
+        (&raw[..raw.len() - 2], "\r\n")
+    } else if raw.ends_with(b"\n") {
+        (&raw[..raw.len() - 1], "\n")
+    } else {
+        (raw, "")
+    }
+}
 
 // the actual tree
 
-type TextTree = Vec<TextNode>;
+pub type TextTree = Vec<TextNode>;
+
+// layout of a directive line, kept so that it can be written back as read
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LineStyle {
+    pub indent: String,      // leading whitespace
+    pub eol: String,         // line terminator ("\n", "\r\n" or "" at end of input)
+    pub trailer: String,     // text following the right separator
+    pub raw: Option<String>, // text between the separators, possibly spanning lines
+    pub lineno: usize,       // line number in source (0 if generated)
+}
+
+impl Default for LineStyle {
+    fn default() -> LineStyle {
+        LineStyle {
+            indent: String::new(),
+            eol: "\n".to_string(),
+            trailer: String::new(),
+            raw: None,
+            lineno: 0,
+        }
+    }
+}
+
+impl LineStyle {
+    // style of a single line replacing a whole block
+    fn collapse(begin: &LineStyle, end: &LineStyle) -> LineStyle {
+        LineStyle {
+            indent: begin.indent.clone(),
+            eol: end.eol.clone(),
+            trailer: begin.trailer.clone(),
+            raw: begin.raw.clone(),
+            lineno: begin.lineno,
+        }
+    }
+
+    // the last source line of the directive
+    pub fn last_lineno(&self) -> usize {
+        self.lineno + self.raw.as_ref().map_or(0, |raw| raw.matches('\n').count())
+    }
+}
+
+// DATA lines a payload was read from: their layout and byte counts,
+// empty for a payload that is written in the default layout
+
+pub type DataLines = Vec<(LineStyle, usize)>;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum TextNode {
-    Plain(String),
-    Data(Vec<u8>),
+    Plain(Vec<u8>), // raw lines, including line terminators
+    Data(Vec<u8>, DataLines),
     Stored {
         keyw: String,
         cas: String,
+        line: LineStyle,
     },
     Encrypted {
         keyw: String,
         txt: TextTree,
         extfields: BTreeMap<String, String>,
+        begin: LineStyle, // ENCRYPTED (and DATA) lines
+        end: LineStyle,   // END line
     },
     BeginEnd {
         keyw: String,
         txt: TextTree,
+        begin: LineStyle,
+        end: LineStyle,
     },
 }
 
 type Parser = fn(
     &[&str],
-    &String,
-    i32,
+    &LineStyle,
     &mut ParseOps,
     &mut Vec<TextNode>,
     &mut Vec<TextNode>,
-) -> Result<(), &'static str>;
+) -> Result<(), ParseErrorKind>;
 
 fn parse_data(
     cmd: &[&str],
-    line: &String,
-    lineno: i32,
-    paops: &mut ParseOps,
+    line: &LineStyle,
+    _paops: &mut ParseOps,
     _pstack: &mut Vec<TextNode>,
     text: &mut Vec<TextNode>,
-) -> Result<(), &'static str> {
+) -> Result<(), ParseErrorKind> {
+    let mut data = Vec::new();
     for i in 0..cmd.len() {
-        let mut data = match utils::base64_decode(cmd[i]) {
-            Ok(data) => data,
-            Err(e) => {
-                eprintln!(
-                    "Parse: Error decoding base64.\n\
-                     Parse: '{}': {}\n\
-                     {}:{}:{}",
-                    cmd[i], e, paops.fname, lineno, line
-                );
-                return Err("Parse error");
-            }
-        };
-
-        // combine with previous
-        if let Some(TextNode::Data(last)) = text.last_mut() {
-            last.append(&mut data);
-        } else {
-            text.push(TextNode::Data(data));
-        }
+        data.append(
+            &mut utils::base64_decode(cmd[i])
+                .map_err(|_| ParseErrorKind::Base64(cmd[i].to_string()))?,
+        );
+    }
+    let len = data.len();
+
+    // combine with previous
+    if let Some(TextNode::Data(last, lines)) = text.last_mut() {
+        last.append(&mut data);
+        lines.push((line.clone(), len));
+    } else {
+        text.push(TextNode::Data(data, vec![(line.clone(), len)]));
     }
     return Ok(());
 }
 
 fn parse_begin(
     cmd: &[&str],
-    line: &String,
-    lineno: i32,
+    line: &LineStyle,
     paops: &mut ParseOps,
     pstack: &mut Vec<TextNode>,
     text: &mut Vec<TextNode>,
-) -> Result<(), &'static str> {
+) -> Result<(), ParseErrorKind> {
     if cmd.len() != 1 {
-        eprintln!(
-            "Parse: BEGIN needs a single keyword.\n\
-             {}:{}:{}",
-            paops.fname, lineno, line
-        );
-        return Err("Parse error");
+        return Err(ParseErrorKind::BeginArgs);
     }
-    paops.level += 1;
+    enter_section(paops)?;
     pstack.push(TextNode::BeginEnd {
         keyw: cmd[0].to_owned(),
         txt: text.to_vec(),
+        begin: line.clone(),
+        end: LineStyle::default(),
     });
     text.clear();
     Ok(())
 }
 
+// nested contents, such as decrypted ones, start beyond the maximum depth
+fn depth_exceeded(paops: &ParseOps) -> EnprotError {
+    EnprotError::Parse {
+        file: paops.fname.clone(),
+        line: 1,
+        col: 1,
+        kind: ParseErrorKind::MaxDepth(paops.max_depth),
+    }
+}
+
+// descend into a BEGIN or ENCRYPTED section
+fn enter_section(paops: &mut ParseOps) -> Result<(), ParseErrorKind> {
+    paops.level += 1;
+    if paops.max_depth != 0 && paops.level > paops.max_depth {
+        return Err(ParseErrorKind::MaxDepth(paops.max_depth));
+    }
+    Ok(())
+}
+
 // parse trailing extended fields, such as pbkdf:
-fn parse_encrypted_extfields(cmd: &[&str]) -> Result<BTreeMap<String, String>, &'static str> {
+fn parse_encrypted_extfields(cmd: &[&str]) -> Result<BTreeMap<String, String>, ParseErrorKind> {
     let mut extfields: BTreeMap<String, String> = BTreeMap::new();
     for field in cmd.iter().rev() {
-        if field.find(':') == None {
-            // extended fields always come at the end
+        // extended fields always come at the end, after any CAS identifier
+        // such as sha256:...
+        if !field.contains(':') || cas::split_id(field).is_some() {
             break;
         }
         let fields = field.splitn(2, ':').collect::<Vec<&str>>();
         let key = fields[0];
         let value = fields[1];
         if extfields.contains_key(key) {
-            return Err("Duplicate extended field");
+            return Err(ParseErrorKind::DuplicateExtField(key.to_string()));
         }
         extfields.insert(key.to_string(), value.to_string());
     }
@@ -227,37 +331,43 @@ fn parse_encrypted_extfields(cmd: &[&str]) -> Result<BTreeMap<String, String>, &
 
 fn parse_encrypted(
     cmd: &[&str],
-    line: &String,
-    lineno: i32,
+    line: &LineStyle,
     paops: &mut ParseOps,
     pstack: &mut Vec<TextNode>,
     text: &mut Vec<TextNode>,
-) -> Result<(), &'static str> {
+) -> Result<(), ParseErrorKind> {
     let extfields = parse_encrypted_extfields(cmd)?;
     let param_count = cmd.len() - extfields.len();
     let extfield_keys: HashSet<String> = extfields.keys().map(|f| f.to_string()).collect();
-    let known_extfields: HashSet<String> = ["pbkdf".to_string(), "cipher".to_string()]
-        .iter()
-        .cloned()
-        .collect();
-    if extfield_keys
-        .difference(&known_extfields)
-        .peekable()
-        .peek()
-        .is_some()
+    let known_extfields: HashSet<String> = [
+        "pbkdf".to_string(),
+        "cipher".to_string(),
+        "version".to_string(),
+    ]
+    .iter()
+    .cloned()
+    .collect();
+    if paops.verbose
+        && extfield_keys
+            .difference(&known_extfields)
+            .any(|key| !key.starts_with("recipient.") && !key.starts_with("password."))
     {
-        eprintln!("Warning: Unrecognized extended field(s) present");
+        paops
+            .notes
+            .push("Warning: Unrecognized extended field(s) present".to_string());
     }
     match param_count {
         1 => {
             // immediate data
             // <( ENCRYPTED Agent_007 )>
             // <( ENCRYPTED Agent_007 pbkdf:... )>
-            paops.level += 1;
+            enter_section(paops)?;
             pstack.push(TextNode::Encrypted {
                 keyw: cmd[0].to_owned(),
                 txt: text.to_vec(),
                 extfields,
+                begin: line.clone(),
+                end: LineStyle::default(),
             });
             text.clear();
             return Ok(());
@@ -266,61 +376,51 @@ fn parse_encrypted(
             // CAS parameter
             // <( ENCRYPTED Agent_007 7a8da017c0fe671ba16f4bc55b884444e708849290d8366f19c552c90950b8c2 )>
             // <( ENCRYPTED Agent_007 7a8da017c0fe671ba16f4bc55b884444e708849290d8366f19c552c90950b8c2 pbkdf:... )>
-            if cmd[1].len() != 64 {
-                return Err("Invalid CAS identifier");
+            if cas::split_id(cmd[1]).is_none() {
+                return Err(ParseErrorKind::InvalidCasId(cmd[1].to_string()));
             }
             let node = vec![TextNode::Stored {
                 keyw: "ct".to_string(),
                 cas: cmd[1].to_string(),
+                line: LineStyle::default(),
             }];
             text.push(TextNode::Encrypted {
                 keyw: cmd[0].to_string(),
                 txt: node,
                 extfields,
+                begin: line.clone(),
+                end: line.clone(),
             });
         }
-        _ => {
-            eprintln!(
-                "Parse: ENCRYPTED has wrong number of \
-                 parameters ({}).\n{}:{}:{}",
-                param_count, paops.fname, lineno, line
-            );
-            return Err("Parse error");
-        }
+        _ => return Err(ParseErrorKind::EncryptedArgs(param_count)),
     }
     return Ok(());
 }
 
 fn parse_end(
     cmd: &[&str],
-    line: &String,
-    lineno: i32,
+    line: &LineStyle,
     paops: &mut ParseOps,
     pstack: &mut Vec<TextNode>,
     text: &mut Vec<TextNode>,
-) -> Result<(), &'static str> {
+) -> Result<(), ParseErrorKind> {
     if cmd.len() > 1 {
-        eprintln!(
-            "Parse: Unknown padding in END.\n{}:{}:{}",
-            paops.fname, lineno, line
-        );
-        return Err("Parse error");
+        return Err(ParseErrorKind::EndPadding);
     }
     match pstack.pop() {
-        Some(TextNode::BeginEnd { keyw, txt }) => {
+        Some(TextNode::BeginEnd {
+            keyw, txt, begin, ..
+        }) => {
             // keyword mismatch ?
             if cmd.len() >= 1 && keyw != cmd[0] {
-                eprintln!(
-                    "Parse: END mismatch (expected '{}').\n\
-                     {}:{}:{}",
-                    keyw, paops.fname, lineno, line
-                );
-                return Err("Parse error");
+                return Err(ParseErrorKind::EndMismatch(keyw));
             }
 
             let node = TextNode::BeginEnd {
                 keyw: keyw,
                 txt: text.to_vec(),
+                begin,
+                end: line.clone(),
             };
             *text = txt;
             text.push(node);
@@ -330,127 +430,176 @@ fn parse_end(
             keyw,
             txt,
             extfields,
+            begin,
+            ..
         }) => {
             // keyword mismatch ?
-            if keyw != cmd[0] {
-                eprintln!(
-                    "Parse: END mismatch (expected '{}').\n\
-                     {}:{}:{}",
-                    keyw, paops.fname, lineno, line
-                );
-                return Err("Parse error");
+            if cmd.len() >= 1 && keyw != cmd[0] {
+                return Err(ParseErrorKind::EndMismatch(keyw));
             }
             // check that the contents are right type
             if text.len() != 1 {
-                eprintln!(
-                    "Parse: {} elements in encrypted {} \
-                     (must be a single DATA or STORED).\n{}:{}:{}",
-                    text.len(),
+                return Err(ParseErrorKind::EncryptedContents {
                     keyw,
-                    paops.fname,
-                    lineno,
-                    line
-                );
-                return Err("Parse error");
+                    count: text.len(),
+                });
             }
             match text[0] {
-                TextNode::Data(_) | TextNode::Stored { .. } => {
+                TextNode::Data(..) | TextNode::Stored { .. } => {
                     let node = TextNode::Encrypted {
                         keyw: keyw,
                         txt: text.to_vec(),
                         extfields,
+                        begin,
+                        end: line.clone(),
                     };
                     *text = txt;
                     text.push(node);
                     paops.level -= 1;
                 }
-                _ => {
-                    eprintln!(
-                        "Parse: not DATA or STORED \
-                         element in encrypted {}.\n{}:{}:{}",
-                        keyw, paops.fname, lineno, line
-                    );
-                    return Err("Parse error");
-                }
+                _ => return Err(ParseErrorKind::EncryptedNotData(keyw)),
             }
         }
-        _ => {
-            eprintln!(
-                "Parse: END without a start clause.\n{}:{}:{}",
-                paops.fname, lineno, line
-            );
-            return Err("Parse error");
-        }
+        _ => return Err(ParseErrorKind::EndWithoutStart),
     }
     Ok(())
 }
 
 fn parse_stored(
     cmd: &[&str],
-    line: &String,
-    lineno: i32,
-    paops: &mut ParseOps,
+    line: &LineStyle,
+    _paops: &mut ParseOps,
     _pstack: &mut Vec<TextNode>,
     text: &mut Vec<TextNode>,
-) -> Result<(), &'static str> {
+) -> Result<(), ParseErrorKind> {
     if cmd.len() != 2 {
-        eprintln!(
-            "Parse: STORED needs two parameters.\n{}:{}:{}",
-            paops.fname, lineno, line
-        );
-        return Err("Parse error");
+        return Err(ParseErrorKind::StoredArgs);
     }
     text.push(TextNode::Stored {
         keyw: cmd[0].to_owned(),
         cas: cmd[1].to_owned(),
+        line: line.clone(),
     });
     Ok(())
 }
 
-pub fn parse<R>(buf_in: R, paops: &mut ParseOps) -> Result<TextTree, &'static str>
+pub fn parse<R>(buf_in: R, paops: &mut ParseOps) -> Result<TextTree, EnprotError>
 where
     R: BufRead,
 {
     if paops.max_depth != 0 && paops.level > paops.max_depth {
-        panic!("Maximum recursion depth!");
+        return Err(depth_exceeded(paops));
     }
 
+    // restore the level even if parsing stops half-way
+    let level = paops.level;
+    let result = parse_lines(buf_in, paops);
+    paops.level = level;
+    result
+}
+
+fn parse_lines<R>(mut buf_in: R, paops: &mut ParseOps) -> Result<TextTree, EnprotError>
+where
+    R: BufRead,
+{
     let mut text = Vec::new(); // the vector of TextNodes
     let mut lineno = 0; // line number in source
     let mut pstack = Vec::new(); // stack
-
-    for line_in in buf_in.lines() {
-        let line = line_in.expect("read error");
+    let mut raw = Vec::new(); // current line, including terminator
+    let left_sep = paops.left_sep.clone();
+    let right_sep = paops.right_sep.clone();
+
+    loop {
+        raw.clear();
+        let len = buf_in
+            .read_until(b'\n', &mut raw)
+            .map_err(|io| EnprotError::Io {
+                path: paops.fname.clone(),
+                io,
+            })?;
+        if len == 0 {
+            break;
+        }
         lineno += 1;
 
-        if !line.trim_start().starts_with(&paops.left_sep) {
+        // a byte order mark is kept as plain text
+        if lineno == 1 && raw.starts_with(UTF8_BOM) {
+            text.push(TextNode::Plain(UTF8_BOM.to_vec()));
+            raw.drain(..UTF8_BOM.len());
+        }
+
+        let (line, eol) = split_eol(&raw);
+        let indent_len = line.iter().take_while(|c| c.is_ascii_whitespace()).count();
+        if !line[indent_len..].starts_with(left_sep.as_bytes()) {
             // combine with previous
             if let Some(TextNode::Plain(last)) = text.last_mut() {
-                last.push('\n');
-                *last += &line;
+                last.extend_from_slice(&raw);
                 continue;
             }
 
-            text.push(TextNode::Plain(line.clone()));
+            text.push(TextNode::Plain(raw.clone()));
             continue;
         }
 
         // we have a command
-        let mut trimmed = line.trim().replacen(&paops.left_sep, "", 1);
-
-        // create a vector out of it
-        if !trimmed.ends_with(&paops.right_sep) {
-            eprintln!(
-                "Parse: Right separator '{}' missing.\n\
-                 {}:{}:{}",
-                paops.right_sep, paops.fname, lineno, line
-            );
-            return Err("Parse error");
-        }
+        let start = lineno;
+        let col = indent_len + 1;
+        let parse_err = |paops: &ParseOps, kind| EnprotError::Parse {
+            file: paops.fname.clone(),
+            line: start,
+            col,
+            kind,
+        };
+        let mut raw_cmd = match str::from_utf8(&line[indent_len + left_sep.len()..]) {
+            Ok(raw_cmd) => raw_cmd.to_string(),
+            Err(_) => return Err(parse_err(paops, ParseErrorKind::InvalidUtf8)),
+        };
+        let indent = String::from_utf8_lossy(&line[..indent_len]).into_owned();
+        let mut eol = eol.to_string();
+
+        // the right separator may be on a later line, as in block comments
+        let trailer = loop {
+            if let Some(i) = raw_cmd.find(&right_sep) {
+                let trailer = raw_cmd[i + right_sep.len()..].to_string();
+                raw_cmd.truncate(i);
+                break trailer;
+            }
+            let mut next = Vec::new();
+            let len = buf_in
+                .read_until(b'\n', &mut next)
+                .map_err(|io| EnprotError::Io {
+                    path: paops.fname.clone(),
+                    io,
+                })?;
+            if len == 0 {
+                return Err(parse_err(
+                    paops,
+                    ParseErrorKind::RightSeparatorMissing(right_sep.clone()),
+                ));
+            }
+            lineno += 1;
+            let (next_line, next_eol) = split_eol(&next);
+            match str::from_utf8(next_line) {
+                Ok(next_line) => {
+                    raw_cmd += &eol;
+                    raw_cmd += next_line;
+                }
+                Err(_) => return Err(parse_err(paops, ParseErrorKind::InvalidUtf8)),
+            }
+            eol = next_eol.to_string();
+        };
 
-        let i = trimmed.len() - paops.right_sep.len();
-        trimmed.truncate(i);
-        let cmd: Vec<&str> = trimmed.split_whitespace().collect();
+        let cmd: Vec<&str> = raw_cmd.split_whitespace().collect();
+        if cmd.is_empty() {
+            return Err(parse_err(paops, ParseErrorKind::EmptyDirective));
+        }
+        let style = LineStyle {
+            indent,
+            eol,
+            trailer,
+            raw: Some(raw_cmd.clone()),
+            lineno: start,
+        };
 
         let mut cmd_parsers: HashMap<&str, Parser> = HashMap::new();
         cmd_parsers.insert("DATA", parse_data);
@@ -460,62 +609,57 @@ where
         cmd_parsers.insert("STORED", parse_stored);
         match cmd_parsers.get(cmd[0]) {
             Some(parser) => {
-                parser(&cmd[1..], &line, lineno, paops, &mut pstack, &mut text)?;
+                if let Err(kind) = parser(&cmd[1..], &style, paops, &mut pstack, &mut text) {
+                    return Err(parse_err(paops, kind));
+                }
                 continue;
             }
             _ => {
-                eprintln!(
-                    "Parse: Unknown section '{}' at\n{}:{}:{}",
-                    cmd[0], paops.fname, lineno, line
-                );
-                return Err("Parse error");
-            }
-        }
-    }
-
-    if pstack.len() > 0 {
-        loop {
-            match pstack.pop() {
-                Some(TextNode::BeginEnd { keyw, txt: _ }) => {
-                    eprintln!("Parse: BEGIN {} without END.", keyw);
-                }
-                Some(TextNode::Encrypted {
-                    keyw,
-                    txt: _,
-                    extfields: _,
-                }) => {
-                    eprintln!("Parse: ENCRYPTED {} without END.", keyw);
-                }
-                _ => return Err("Unexpected end"),
+                return Err(parse_err(
+                    paops,
+                    ParseErrorKind::UnknownSection(cmd[0].to_string()),
+                ));
             }
         }
     }
 
-    Ok(text)
+    // report the innermost unterminated section
+    let (section, keyw) = match pstack.pop() {
+        None => return Ok(text),
+        Some(TextNode::BeginEnd { keyw, .. }) => ("BEGIN", keyw),
+        Some(TextNode::Encrypted { keyw, .. }) => ("ENCRYPTED", keyw),
+        Some(_) => ("section", String::new()),
+    };
+    Err(EnprotError::Parse {
+        file: paops.fname.clone(),
+        line: lineno,
+        col: 1,
+        kind: ParseErrorKind::Unterminated { section, keyw },
+    })
 }
 
 // recursive unparser
 
-pub fn tree_write<W: Write>(outw: &mut W, text: &TextTree, paops: &mut ParseOps) {
+pub fn tree_write<W: Write>(outw: &mut W, text: &TextTree, paops: &mut ParseOps) -> io::Result<()> {
     for elem in text {
         match elem {
             // Plain chunk of text
-            TextNode::Plain(line) => {
-                writeln!(outw, "{}", line).unwrap();
+            TextNode::Plain(raw) => {
+                outw.write_all(raw)?;
             }
 
             // BEGIN-END block
-            TextNode::BeginEnd { keyw, txt } => {
-                writeln!(
-                    outw,
-                    "{} BEGIN {} {}",
-                    paops.left_sep, keyw, paops.right_sep
-                )
-                .unwrap();
+            TextNode::BeginEnd {
+                keyw,
+                txt,
+                begin,
+                end,
+            } => {
+                directive_write(outw, begin, &format!("BEGIN {}", keyw), paops)?;
                 paops.level += 1;
-                tree_write(outw, txt, paops);
+                tree_write(outw, txt, paops)?;
                 paops.level -= 1;
-                writeln!(outw, "{} END {} {}", paops.left_sep, keyw, paops.right_sep).unwrap();
+                directive_write(outw, end, &format!("END {}", keyw), paops)?;
             }
 
             // ENCRYPTED block
@@ -523,67 +667,119 @@ pub fn tree_write<W: Write>(outw: &mut W, text: &TextTree, paops: &mut ParseOps)
                 keyw,
                 txt,
                 ref extfields,
+                begin,
+                end,
             } => {
-                write!(outw, "{} ENCRYPTED {}", paops.left_sep, keyw).unwrap();
-                if let TextNode::Stored { keyw: _, ref cas } = txt[0] {
+                let mut header = format!("ENCRYPTED {}", keyw);
+                if let Some(TextNode::Stored { cas, .. }) = txt.first() {
                     // Encrypted+Stored
-                    write!(outw, " {}", cas).unwrap();
+                    header += &format!(" {}", cas);
                     for (key, value) in extfields.iter() {
-                        write!(outw, " {}:{}", key, value).unwrap();
+                        header += &format!(" {}:{}", key, value);
                     }
-                    writeln!(outw, " {}", paops.right_sep).unwrap();
+                    directive_write(outw, &LineStyle::collapse(begin, end), &header, paops)?;
                 } else {
                     // Encrypted
                     for (key, value) in extfields.iter() {
-                        write!(outw, " {}:{}", key, value).unwrap();
+                        header += &format!(" {}:{}", key, value);
                     }
-                    writeln!(outw, " {}", paops.right_sep).unwrap();
-                    paops.level += 1;
-                    tree_write(outw, txt, paops);
-                    paops.level -= 1;
-                    writeln!(outw, "{} END {} {}", paops.left_sep, keyw, paops.right_sep).unwrap();
+                    directive_write(outw, begin, &header, paops)?;
+                    // new DATA lines follow the layout of the header
+                    for node in txt {
+                        if let TextNode::Data(data, lines) = node {
+                            data_write(outw, data, lines, begin, paops)?;
+                        }
+                    }
+                    directive_write(outw, end, &format!("END {}", keyw), paops)?;
                 }
             }
 
             // STORED
-            TextNode::Stored { keyw, cas } => {
-                writeln!(
-                    outw,
-                    "{} STORED {} {} {}",
-                    paops.left_sep, keyw, cas, paops.right_sep
-                )
-                .unwrap();
+            TextNode::Stored { keyw, cas, line } => {
+                directive_write(outw, line, &format!("STORED {} {}", keyw, cas), paops)?;
             }
+
             // DATA
-            TextNode::Data(data) => {
-                for line in data.chunks(DATA_BYTES_PER_LINE) {
-                    writeln!(
-                        outw,
-                        "{} DATA {} {}",
-                        paops.left_sep,
-                        utils::base64_encode(line).unwrap(),
-                        paops.right_sep
-                    )
-                    .unwrap();
-                }
+            TextNode::Data(data, lines) => {
+                data_write(outw, data, lines, &LineStyle::default(), paops)?
             }
         }
     }
+    Ok(())
 }
 
-// perform ops
+fn directive_write<W: Write>(
+    outw: &mut W,
+    line: &LineStyle,
+    cmd: &str,
+    paops: &ParseOps,
+) -> io::Result<()> {
+    // keep the original spelling of a directive that is written back unchanged
+    let body = match line.raw {
+        Some(ref raw) if raw.split_whitespace().eq(cmd.split_whitespace()) => raw.clone(),
+        _ => format!(" {} ", cmd),
+    };
+    write!(
+        outw,
+        "{}{}{}{}{}{}",
+        line.indent, paops.left_sep, body, paops.right_sep, line.trailer, line.eol
+    )
+}
 
-pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTree, &'static str> {
-    let mut text_out = Vec::new();
+fn data_write<W: Write>(
+    outw: &mut W,
+    data: &[u8],
+    lines: &DataLines,
+    line: &LineStyle,
+    paops: &ParseOps,
+) -> io::Result<()> {
+    let mut layout: Vec<(&LineStyle, &[u8])> = Vec::new();
+    if !lines.is_empty() && lines.iter().map(|(_, len)| len).sum::<usize>() == data.len() {
+        // keep the lines the payload was read from
+        let mut rest = data;
+        for (style, len) in lines {
+            let (chunk, next) = rest.split_at(*len);
+            layout.push((style, chunk));
+            rest = next;
+        }
+    } else {
+        for chunk in data.chunks(DATA_BYTES_PER_LINE) {
+            layout.push((line, chunk));
+        }
+    }
+    for (style, chunk) in layout {
+        let encoded = utils::base64_encode(chunk).map_err(|e| io::Error::other(e.to_string()))?;
+        directive_write(outw, style, &format!("DATA {}", encoded), paops)?;
+    }
+    Ok(())
+}
 
+// perform ops
+
+pub fn transform(text_in: &TextTree, paops: &mut ParseOps) -> Result<TextTree, EnprotError> {
     if paops.max_depth != 0 && paops.level > paops.max_depth {
-        panic!("Maximum recursion depth!");
+        return Err(depth_exceeded(paops));
     }
 
+    // restore the level even if the transform fails half-way
+    let level = paops.level;
+    let result = transform_nodes(text_in, paops);
+    paops.level = level;
+    result
+}
+
+fn transform_nodes(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTree, EnprotError> {
+    let mut text_out = Vec::new();
+
     for elem in text_in {
         match elem {
             // BEGIN-END
-            TextNode::BeginEnd { ref keyw, ref txt } => {
+            TextNode::BeginEnd {
+                ref keyw,
+                ref txt,
+                ref begin,
+                ref end,
+            } => {
                 // encrypt it ?
                 if paops.encrypt.contains(keyw) {
                     paops.level += 1;
@@ -591,25 +787,29 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
                     paops.level -= 1;
 
                     // get blob
-                    let pt = tree_to_blob(&block, paops);
-                    // get password
+                    let pt = tree_to_blob(&block, paops)?;
+                    // get password, which recipients make optional
                     let (newpass, pass) = match paops.passwords.get(keyw) {
-                        Some(pass) => (false, pass.to_string()),
-                        None => (true, prot::get_password(&keyw, true)),
+                        Some(pass) => (false, Some(pass.to_string())),
+                        None if !paops.recipients.is_empty() => (false, None),
+                        None => (true, Some(prot::get_password(&keyw, true)?)),
                     };
                     if newpass {
                         paops
                             .passwords
-                            .insert(keyw.clone().to_string(), pass.clone());
+                            .insert(keyw.clone().to_string(), pass.clone().unwrap());
                     }
 
                     // encrypt
                     let (ct, extfields) = prot::encrypt(
+                        keyw,
                         pt,
-                        &pass,
+                        pass.as_deref(),
+                        &paops.recipients,
                         &paops.rng,
                         &paops.pbkdfopts,
                         &paops.cipheropts,
+                        &paops.document_id,
                         &mut paops.pbkdf_cache,
                         &paops.policy,
                     )?;
@@ -620,14 +820,17 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
                         vec![TextNode::Stored {
                             keyw: "ct".to_string(),
                             cas: hexhash,
+                            line: LineStyle::default(),
                         }]
                     } else {
-                        vec![TextNode::Data(ct)]
+                        vec![TextNode::Data(ct, Vec::new())]
                     };
                     text_out.push(TextNode::Encrypted {
                         keyw: keyw.to_string(),
                         txt: node,
                         extfields,
+                        begin: begin.clone(),
+                        end: end.clone(),
                     });
                     continue;
                 }
@@ -638,11 +841,12 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
                     let block = transform(&txt.to_vec(), paops)?;
                     paops.level -= 1;
 
-                    let blob = tree_to_blob(&block, paops);
+                    let blob = tree_to_blob(&block, paops)?;
                     let hexhash = cas::save(blob, paops)?;
                     text_out.push(TextNode::Stored {
                         keyw: keyw.to_string(),
                         cas: hexhash,
+                        line: LineStyle::collapse(begin, end),
                     });
                     continue;
                 };
@@ -655,6 +859,8 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
                 text_out.push(TextNode::BeginEnd {
                     keyw: keyw.to_string(),
                     txt: block,
+                    begin: begin.clone(),
+                    end: end.clone(),
                 });
                 continue;
             }
@@ -664,45 +870,148 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
                 ref keyw,
                 ref txt,
                 ref extfields,
+                ref begin,
+                ref end,
             } => {
-                // decrypt it
-                if paops.decrypt.contains(keyw) {
-                    // get ciphertext
-                    let ct = match txt[0] {
-                        TextNode::Data(ref data) => data.to_vec(),
-                        TextNode::Stored {
-                            keyw: _,
-                            cas: ref hexhash,
-                        } => cas::load(&hexhash, paops)?,
-                        _ => panic!("No data in ENCRYPTED."),
+                // re-encrypt it under a new password ?
+                if paops.rekey.contains(keyw) {
+                    let ct = encrypted_ct(keyw, txt, paops)?;
+                    if prot::has_data_key(extfields) {
+                        let (old, (_, key)) = open_with_password(keyw, ct, extfields, paops)?;
+
+                        // get the new password
+                        let pass = match paops.new_passwords.get(keyw) {
+                            Some(pass) => pass.to_string(),
+                            None => prot::get_password(&format!("{} (new)", keyw), true)?,
+                        };
+                        paops.new_passwords.insert(keyw.to_string(), pass.clone());
+
+                        // the ciphertext and the other stanzas stay as they are
+                        let extfields = prot::replace_password(
+                            &key,
+                            &old,
+                            &pass,
+                            extfields,
+                            &paops.rng,
+                            &paops.pbkdfopts,
+                            &mut paops.pbkdf_cache,
+                            &paops.policy,
+                        )?;
+                        text_out.push(TextNode::Encrypted {
+                            keyw: keyw.to_string(),
+                            txt: txt.clone(),
+                            extfields,
+                            begin: begin.clone(),
+                            end: end.clone(),
+                        });
+                        continue;
+                    }
+                    // a key derived from the password isn't kept, nor the stanzas wrapping it
+                    if !prot::password_stanzas(extfields).is_empty() {
+                        return Err(EnprotError::Crypto(
+                            "Rekey would drop the other passwords of a segment without a data key",
+                        ));
+                    }
+                    let pt = decrypt_ct(keyw, ct, extfields, paops)?;
+
+                    // get the new password
+                    let pass = match paops.new_passwords.get(keyw) {
+                        Some(pass) => pass.to_string(),
+                        None => prot::get_password(&format!("{} (new)", keyw), true)?,
                     };
+                    paops.new_passwords.insert(keyw.to_string(), pass.clone());
 
-                    // get password
-                    let (newpass, pass) = match paops.passwords.get(keyw) {
-                        Some(pass) => (false, pass.to_string()),
-                        None => (true, prot::get_password(keyw, false)),
+                    let (ct, extfields) = prot::encrypt(
+                        keyw,
+                        pt,
+                        Some(&pass),
+                        &paops.recipients,
+                        &paops.rng,
+                        &paops.pbkdfopts,
+                        &paops.cipheropts,
+                        &paops.document_id,
+                        &mut paops.pbkdf_cache,
+                        &paops.policy,
+                    )?;
+
+                    // keep the ciphertext where it was
+                    let node = match txt.first() {
+                        Some(TextNode::Stored { keyw, line, .. }) => vec![TextNode::Stored {
+                            keyw: keyw.to_string(),
+                            cas: cas::save(ct, paops)?,
+                            line: line.clone(),
+                        }],
+                        _ => vec![TextNode::Data(ct, Vec::new())],
                     };
-                    if newpass {
-                        paops
-                            .passwords
-                            .insert(keyw.clone().to_string(), pass.clone());
-                    }
+                    text_out.push(TextNode::Encrypted {
+                        keyw: keyw.to_string(),
+                        txt: node,
+                        extfields,
+                        begin: begin.clone(),
+                        end: end.clone(),
+                    });
+                    continue;
+                }
 
-                    // decrypt
-                    let pt = match prot::decrypt(
-                        ct,
+                // wrap its data key for another password ?
+                if paops.access_add.contains(keyw) {
+                    let ct = encrypted_ct(keyw, txt, paops)?;
+                    let (_, key) = unlock_ct(keyw, ct, extfields, paops)?;
+
+                    // get the added password
+                    let pass = match paops.new_passwords.get(keyw) {
+                        Some(pass) => pass.to_string(),
+                        None => prot::get_password(&format!("{} (new)", keyw), true)?,
+                    };
+                    paops.new_passwords.insert(keyw.to_string(), pass.clone());
+
+                    let extfields = prot::add_password(
+                        &key,
                         &pass,
-                        &extfields.get("pbkdf"),
-                        &extfields.get("cipher"),
+                        extfields,
+                        &paops.rng,
+                        &paops.pbkdfopts,
                         &mut paops.pbkdf_cache,
                         &paops.policy,
-                    ) {
-                        Ok(ct) => ct.to_vec(),
-                        Err(e) => {
-                            eprintln!("Error decrypting {}: {}.", &keyw, e);
-                            return Err(e);
-                        }
+                    )?;
+                    text_out.push(TextNode::Encrypted {
+                        keyw: keyw.to_string(),
+                        txt: txt.clone(),
+                        extfields,
+                        begin: begin.clone(),
+                        end: end.clone(),
+                    });
+                    continue;
+                }
+
+                // drop the stanzas of a password ?
+                if paops.access_remove.contains(keyw) {
+                    let pass = match paops.passwords.get(keyw) {
+                        Some(pass) => pass.to_string(),
+                        None => prot::get_password(&format!("{} (to remove)", keyw), false)?,
                     };
+                    paops.passwords.insert(keyw.to_string(), pass.clone());
+
+                    let extfields = prot::remove_password(
+                        &pass,
+                        extfields,
+                        &mut paops.pbkdf_cache,
+                        &paops.policy,
+                    )?;
+                    text_out.push(TextNode::Encrypted {
+                        keyw: keyw.to_string(),
+                        txt: txt.clone(),
+                        extfields,
+                        begin: begin.clone(),
+                        end: end.clone(),
+                    });
+                    continue;
+                }
+
+                // decrypt it
+                if paops.decrypt.contains(keyw) {
+                    let ct = encrypted_ct(keyw, txt, paops)?;
+                    let pt = decrypt_ct(keyw, ct, extfields, paops)?;
 
                     // parse to tree
                     let mut block = blob_to_tree(pt, "decrypted".to_string(), &mut paops)?;
@@ -714,47 +1023,44 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
                     text_out.push(TextNode::BeginEnd {
                         keyw: keyw.to_string(),
                         txt: block,
+                        begin: begin.clone(),
+                        end: end.clone(),
                     });
                     continue;
                 } else {
                     // store (store) ciphertext
                     if paops.store.contains(keyw) {
-                        let hexhash = match txt[0] {
-                            TextNode::Data(ref data) => cas::save(data.to_vec(), paops)?,
-                            TextNode::Stored {
-                                keyw: _,
-                                cas: ref hexhash,
-                            } => hexhash.to_string(),
-                            _ => panic!("No data in ENCRYPTED."),
+                        let hexhash = match txt.first() {
+                            Some(TextNode::Data(data, _)) => cas::save(data.to_vec(), paops)?,
+                            Some(TextNode::Stored { cas: hexhash, .. }) => hexhash.to_string(),
+                            _ => return Err(EnprotError::NoEncryptedData(keyw.to_string())),
                         };
                         let node = vec![TextNode::Stored {
                             keyw: "ct".to_string(),
                             cas: hexhash,
+                            line: LineStyle::default(),
                         }];
                         text_out.push(TextNode::Encrypted {
                             keyw: keyw.to_string(),
                             txt: node,
-                            extfields: BTreeMap::new(),
+                            extfields: extfields.clone(),
+                            begin: LineStyle::collapse(begin, end),
+                            end: LineStyle::collapse(begin, end),
                         });
                         continue;
                     }
 
                     // fetch (include) ciphertext
                     if paops.fetch.contains(keyw) {
-                        let ct = match txt[0] {
-                            TextNode::Data(ref data) => data.to_vec(),
-                            TextNode::Stored {
-                                keyw: _,
-                                cas: ref hexhash,
-                            } => cas::load(&hexhash, paops)?,
-                            _ => panic!("No data in ENCRYPTED."),
-                        };
-                        let node = vec![TextNode::Data(ct)];
+                        let ct = encrypted_ct(keyw, txt, paops)?;
+                        let node = vec![TextNode::Data(ct, Vec::new())];
 
                         text_out.push(TextNode::Encrypted {
                             keyw: keyw.to_string(),
                             txt: node,
-                            extfields: BTreeMap::new(),
+                            extfields: extfields.clone(),
+                            begin: begin.clone(),
+                            end: end.clone(),
                         });
                         continue;
                     };
@@ -764,7 +1070,11 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
             }
 
             // STORED
-            TextNode::Stored { ref keyw, ref cas } => {
+            TextNode::Stored {
+                ref keyw,
+                ref cas,
+                ref line,
+            } => {
                 // fetch it ?
                 if paops.fetch.contains(keyw) {
                     let blob = cas::load(&cas, paops)?;
@@ -777,6 +1087,8 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
                     text_out.push(TextNode::BeginEnd {
                         keyw: keyw.to_string(),
                         txt: block,
+                        begin: line.clone(),
+                        end: line.clone(),
                     });
                     continue;
                 }
@@ -793,20 +1105,115 @@ pub fn transform(text_in: &TextTree, mut paops: &mut ParseOps) -> Result<TextTre
 
 // convenience functions
 
-fn blob_to_tree(
-    data: Vec<u8>,
+// get the ciphertext of an ENCRYPTED block, loading it from CAS if stored
+fn encrypted_ct(keyw: &str, txt: &TextTree, paops: &mut ParseOps) -> Result<Vec<u8>, EnprotError> {
+    match txt.first() {
+        Some(TextNode::Data(data, _)) => Ok(data.to_vec()),
+        Some(TextNode::Stored { cas: hexhash, .. }) => cas::load(hexhash, paops),
+        _ => Err(EnprotError::NoEncryptedData(keyw.to_string())),
+    }
+}
+
+// decrypt the ciphertext of an ENCRYPTED block, asking for the password if needed
+fn decrypt_ct(
+    keyw: &str,
+    ct: Vec<u8>,
+    extfields: &BTreeMap<String, String>,
+    paops: &mut ParseOps,
+) -> Result<Vec<u8>, EnprotError> {
+    Ok(unlock_ct(keyw, ct, extfields, paops)?.0)
+}
+
+// the plaintext and the data key of an ENCRYPTED block
+fn unlock_ct(
+    keyw: &str,
+    ct: Vec<u8>,
+    extfields: &BTreeMap<String, String>,
+    paops: &mut ParseOps,
+) -> Result<(Vec<u8>, Vec<u8>), EnprotError> {
+    let decrypt_err = |e| EnprotError::Decrypt {
+        keyword: keyw.to_string(),
+        source: Box::new(e),
+    };
+
+    // an identity among the recipients needs no password
+    let opened = prot::decrypt_for_identities(
+        keyw,
+        &ct,
+        &paops.identities,
+        extfields,
+        &paops.document_id,
+        &paops.policy,
+    )
+    .map_err(decrypt_err)?;
+    if let Some(opened) = opened {
+        return Ok(opened);
+    }
+    if !prot::password_protected(extfields) {
+        return Err(decrypt_err(EnprotError::Crypto(
+            "No identity matches a recipient of the segment",
+        )));
+    }
+    Ok(open_with_password(keyw, ct, extfields, paops)?.1)
+}
+
+// the password of an ENCRYPTED block, asked for if needed, with its plaintext
+// and data key
+fn open_with_password(
+    keyw: &str,
+    ct: Vec<u8>,
+    extfields: &BTreeMap<String, String>,
+    paops: &mut ParseOps,
+) -> Result<(String, prot::Opened), EnprotError> {
+    let decrypt_err = |e| EnprotError::Decrypt {
+        keyword: keyw.to_string(),
+        source: Box::new(e),
+    };
+
+    // get password
+    let (newpass, pass) = match paops.passwords.get(keyw) {
+        Some(pass) => (false, pass.to_string()),
+        None => (true, prot::get_password(keyw, false)?),
+    };
+    if newpass {
+        paops.passwords.insert(keyw.to_string(), pass.clone());
+    }
+
+    // decrypt
+    let opened = prot::decrypt(
+        keyw,
+        ct,
+        &pass,
+        extfields,
+        &paops.document_id,
+        &mut paops.pbkdf_cache,
+        &paops.policy,
+    )
+    .map_err(decrypt_err)?;
+    Ok((pass, opened))
+}
+
+pub fn blob_to_tree(
+    mut data: Vec<u8>,
     path: String,
     mut paops: &mut ParseOps,
-) -> Result<TextTree, &'static str> {
+) -> Result<TextTree, EnprotError> {
+    // the contents of a block always end with a line terminator
+    if !data.is_empty() && !data.ends_with(b"\n") {
+        data.push(b'\n');
+    }
     paops.fname = path.clone();
     let tree = parse(Cursor::new(data), &mut paops)?;
     Ok(tree)
 }
 
-fn tree_to_blob(text: &TextTree, mut paops: &mut ParseOps) -> Vec<u8> {
+fn tree_to_blob(text: &TextTree, mut paops: &mut ParseOps) -> Result<Vec<u8>, EnprotError> {
     let mut blob = Vec::new();
-    tree_write(&mut blob, text, &mut paops);
-    blob
+    tree_write(&mut blob, text, &mut paops).map_err(|io| EnprotError::Io {
+        path: paops.fname.clone(),
+        io,
+    })?;
+    Ok(blob)
 }
 
 #[cfg(test)]
@@ -864,12 +1271,12 @@ mod tests {
         let outtree = transform(&intree, &mut paops).unwrap();
         // re-parse
         parse(
-            BufReader::new(&tree_to_blob(&outtree, &mut paops)[..]),
+            BufReader::new(&tree_to_blob(&outtree, &mut paops).unwrap()[..]),
             &mut paops,
         )
         .unwrap();
 
-        let buf = tree_to_blob(&outtree, &mut paops);
+        let buf = tree_to_blob(&outtree, &mut paops).unwrap();
         assert_eq!(
             str::from_utf8(&buf).unwrap(),
             &fs::read_to_string("test-data/test-store-agent007.ept").unwrap()
@@ -907,12 +1314,12 @@ mod tests {
         let outtree = transform(&intree, &mut paops).unwrap();
         // re-parse
         parse(
-            BufReader::new(&tree_to_blob(&outtree, &mut paops)[..]),
+            BufReader::new(&tree_to_blob(&outtree, &mut paops).unwrap()[..]),
             &mut paops,
         )
         .unwrap();
 
-        let buf = tree_to_blob(&outtree, &mut paops);
+        let buf = tree_to_blob(&outtree, &mut paops).unwrap();
         assert_eq!(
             str::from_utf8(&buf).unwrap(),
             &fs::read_to_string("test-data/test-store-geheim.ept").unwrap()
@@ -932,7 +1339,7 @@ mod tests {
         paops.store.clear();
         paops.fetch.insert("GEHEIM".to_string());
         let outtree = transform(&intree, &mut paops).unwrap();
-        let buf = tree_to_blob(&outtree, &mut paops);
+        let buf = tree_to_blob(&outtree, &mut paops).unwrap();
         assert_eq!(
             str::from_utf8(&buf).unwrap(),
             &fs::read_to_string("sample/test.ept").unwrap()
@@ -944,6 +1351,8 @@ mod tests {
     fn transform_test_ept_encrypt_decrypt_geheim() {
         let (intree, mut paops, _casdir) = parse_ept("sample/test.ept");
         paops.pbkdfopts.alg = "legacy".to_string();
+        // the data key of the fixtures
+        paops.cipheropts.key = Some((0..64).collect());
         // encrypt
         paops.encrypt.insert("GEHEIM".to_string());
         paops
@@ -952,21 +1361,21 @@ mod tests {
         let outtree = transform(&intree, &mut paops).unwrap();
         // re-parse
         parse(
-            BufReader::new(&tree_to_blob(&outtree, &mut paops)[..]),
+            BufReader::new(&tree_to_blob(&outtree, &mut paops).unwrap()[..]),
             &mut paops,
         )
         .unwrap();
 
-        let buf = tree_to_blob(&outtree, &mut paops);
+        let buf = tree_to_blob(&outtree, &mut paops).unwrap();
         assert_eq!(
             str::from_utf8(&buf).unwrap(),
-            &fs::read_to_string("test-data/test-encrypt-geheim.ept").unwrap()
+            &fs::read_to_string("test-data/test-encrypt-geheim-v2.ept").unwrap()
         );
         // decrypt
         paops.encrypt.clear();
         paops.decrypt.insert("GEHEIM".to_string());
-        let outtree = transform(&intree, &mut paops).unwrap();
-        let buf = tree_to_blob(&outtree, &mut paops);
+        let outtree = transform(&outtree, &mut paops).unwrap();
+        let buf = tree_to_blob(&outtree, &mut paops).unwrap();
         assert_eq!(
             str::from_utf8(&buf).unwrap(),
             &fs::read_to_string("sample/test.ept").unwrap()
@@ -978,6 +1387,8 @@ mod tests {
     fn transform_test_ept_encrypt_store_agent007() {
         let (intree, mut paops, _casdir) = parse_ept("sample/test.ept");
         paops.pbkdfopts.alg = "legacy".to_string();
+        // the data key of the fixtures
+        paops.cipheropts.key = Some((0..64).collect());
         // encrypt & store
         paops.encrypt.insert("Agent_007".to_string());
         paops.store.insert("Agent_007".to_string());
@@ -987,25 +1398,118 @@ mod tests {
         let outtree = transform(&intree, &mut paops).unwrap();
         // re-parse
         parse(
-            BufReader::new(&tree_to_blob(&outtree, &mut paops)[..]),
+            BufReader::new(&tree_to_blob(&outtree, &mut paops).unwrap()[..]),
             &mut paops,
         )
         .unwrap();
 
-        let buf = tree_to_blob(&outtree, &mut paops);
+        let buf = tree_to_blob(&outtree, &mut paops).unwrap();
         assert_eq!(
             str::from_utf8(&buf).unwrap(),
-            &fs::read_to_string("test-data/test-encrypt-store-agent007.ept").unwrap()
+            &fs::read_to_string("test-data/test-encrypt-store-agent007-v2.ept").unwrap()
         );
         // decrypt
         paops.encrypt.clear();
         paops.store.clear();
         paops.decrypt.insert("Agent_007".to_string());
-        let outtree = transform(&intree, &mut paops).unwrap();
-        let buf = tree_to_blob(&outtree, &mut paops);
+        let outtree = transform(&outtree, &mut paops).unwrap();
+        let buf = tree_to_blob(&outtree, &mut paops).unwrap();
         assert_eq!(
             str::from_utf8(&buf).unwrap(),
             &fs::read_to_string("sample/test.ept").unwrap()
         );
     }
+
+    // test that parse errors carry the location of the offending directive
+    #[test]
+    fn parse_error_location() {
+        let mut paops = ParseOps {
+            fname: "bad.ept".to_string(),
+            ..ParseOps::new(Box::new(CryptoPolicyDefault {}))
+        };
+        let doc = "line 1\n  // <( BEGIN A )>\n  // <( END B )>\n";
+        match parse(BufReader::new(doc.as_bytes()), &mut paops) {
+            Err(EnprotError::Parse {
+                file,
+                line,
+                col,
+                kind,
+            }) => {
+                assert_eq!(file, "bad.ept");
+                assert_eq!(line, 3);
+                assert_eq!(col, 3);
+                assert_eq!(kind, ParseErrorKind::EndMismatch("A".to_string()));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    // test that indented markup keeps its indentation through encryption
+    // and decryption, and that DATA lines follow the ENCRYPTED header
+    #[test]
+    fn transform_indented_roundtrip() {
+        let doc = "def f():\n    # <( BEGIN A )>\n    x = 1\n    # <( END A )>\n";
+        let mut paops = ParseOps::new(Box::new(CryptoPolicyDefault {}));
+        paops.left_sep = "# <(".to_string();
+        paops.pbkdfopts.alg = "legacy".to_string();
+        paops
+            .passwords
+            .insert("A".to_string(), "password".to_string());
+        let intree = parse(BufReader::new(doc.as_bytes()), &mut paops).unwrap();
+
+        // encrypt
+        paops.encrypt.insert("A".to_string());
+        let enctree = transform(&intree, &mut paops).unwrap();
+        let buf = String::from_utf8(tree_to_blob(&enctree, &mut paops).unwrap()).unwrap();
+        let lines: Vec<&str> = buf.lines().collect();
+        assert!(lines[1].starts_with("    # <( ENCRYPTED A"));
+        assert!(lines[2].starts_with("    # <( DATA "));
+        assert_eq!(lines[3], "    # <( END A )>");
+
+        // decrypt
+        paops.encrypt.clear();
+        paops.decrypt.insert("A".to_string());
+        let dectree = transform(&enctree, &mut paops).unwrap();
+        let buf = tree_to_blob(&dectree, &mut paops).unwrap();
+        assert_eq!(str::from_utf8(&buf).unwrap(), doc);
+    }
+
+    #[test]
+    fn transform_block_comment_roundtrip() {
+        let doc = "/* <(\n   BEGIN A )> */\nint x = 1;\n/* <( END A )> */\n";
+        let mut paops = ParseOps::new(Box::new(CryptoPolicyDefault {}));
+        paops.left_sep = "/* <(".to_string();
+        paops.pbkdfopts.alg = "legacy".to_string();
+        paops
+            .passwords
+            .insert("A".to_string(), "password".to_string());
+        let intree = parse(BufReader::new(doc.as_bytes()), &mut paops).unwrap();
+        match intree[0] {
+            TextNode::BeginEnd { ref begin, .. } => {
+                assert_eq!(begin.trailer, " */");
+                assert_eq!(begin.raw, Some("\n   BEGIN A ".to_string()));
+            }
+            _ => panic!("expected a BEGIN-END block"),
+        }
+        let buf = tree_to_blob(&intree, &mut paops).unwrap();
+        assert_eq!(str::from_utf8(&buf).unwrap(), doc);
+
+        // encrypt
+        paops.encrypt.insert("A".to_string());
+        let enctree = transform(&intree, &mut paops).unwrap();
+        let buf = String::from_utf8(tree_to_blob(&enctree, &mut paops).unwrap()).unwrap();
+        let lines: Vec<&str> = buf.lines().collect();
+        assert!(lines[0].starts_with("/* <( ENCRYPTED A"));
+        assert!(lines[0].ends_with(" )> */"));
+        assert!(lines[1].starts_with("/* <( DATA "));
+        assert!(lines[1].ends_with(" )> */"));
+        assert_eq!(lines[2], "/* <( END A )> */");
+
+        // decrypt
+        paops.encrypt.clear();
+        paops.decrypt.insert("A".to_string());
+        let dectree = transform(&enctree, &mut paops).unwrap();
+        let buf = tree_to_blob(&dectree, &mut paops).unwrap();
+        assert_eq!(str::from_utf8(&buf).unwrap(), doc);
+    }
 }
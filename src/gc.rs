@@ -0,0 +1,182 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	garbage collection of unreferenced CAS objects
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use cas;
+use consts;
+use document::{Check, CheckStatus};
+use error::EnprotError;
+use etree::ParseOps;
+
+// CAS objects are named by their hash, possibly less a shard directory,
+// with the hash algorithm as extension
+fn is_object_name(name: &str) -> bool {
+    let mut parts = name.splitn(2, '.');
+    let digest = parts.next().unwrap_or("");
+    let alg_known = match parts.next() {
+        Some(alg) => consts::VALID_CAS_HASH_ALGS.contains(&alg),
+        None => true,
+    };
+    // the shortest digest of any algorithm, as found within a shard
+    let min_len = consts::VALID_CAS_HASH_ALGS
+        .iter()
+        .filter_map(|alg| cas::hex_len(alg))
+        .min();
+    min_len.is_some_and(|len| digest.len() >= len - consts::CAS_SHARD_LEN)
+        && digest.chars().all(|c| c.is_ascii_hexdigit())
+        && alg_known
+}
+
+// the files in and under `input`, skipping hidden entries and CAS objects
+pub fn expand(input: &str) -> io::Result<Vec<String>> {
+    let path = Path::new(input);
+    if !path.is_dir() {
+        return Ok(vec![input.to_string()]);
+    }
+    let mut files = Vec::new();
+    let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<fs::DirEntry>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || is_object_name(&name) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        let child = entry.path().to_string_lossy().into_owned();
+        if file_type.is_dir() {
+            files.extend(expand(&child)?);
+        } else if file_type.is_file() {
+            files.push(child);
+        }
+    }
+    Ok(files)
+}
+
+// the objects referenced by the documents seen so far
+#[derive(Default)]
+pub struct Reachable {
+    pub ids: BTreeSet<String>,
+    pub unexamined: Vec<String>, // contents that may hide references
+}
+
+impl Reachable {
+    pub fn add(&mut self, path: &str, checks: &[Check]) {
+        for check in checks {
+            if let Some(ref id) = check.cas {
+                self.ids.insert(id.clone());
+            }
+            match check.status {
+                CheckStatus::NoKey => self.unexamined.push(format!(
+                    "{}: {} lines {}-{}: no key",
+                    path, check.keyword, check.lines.0, check.lines.1
+                )),
+                CheckStatus::Failed(ref e) => self.unexamined.push(format!(
+                    "{}: {} lines {}-{}: {}",
+                    path, check.keyword, check.lines.0, check.lines.1, e
+                )),
+                _ => (),
+            }
+            self.add(path, &check.children);
+        }
+    }
+
+    // the contents that may hide references, if any, listed under header
+    pub fn unexamined_report(&self, header: &str) -> Option<String> {
+        if self.unexamined.is_empty() {
+            return None;
+        }
+        let mut report = header.to_string();
+        for what in &self.unexamined {
+            report += &format!("\n  {}", what);
+        }
+        Some(report)
+    }
+}
+
+// what a collection found and did, for the caller to print
+#[derive(Default)]
+pub struct Collected {
+    pub warning: Option<String>,  // contents that couldn't be examined
+    pub lines: Vec<String>,       // the objects deleted or unreachable, and a summary
+    pub errors: Vec<EnprotError>, // failed deletions
+    pub refused: bool,            // nothing deleted, references may have been missed
+}
+
+impl Collected {
+    pub fn ok(&self) -> bool {
+        self.errors.is_empty() && !self.refused
+    }
+}
+
+// list the unreachable objects of the store written to, deleting them if
+// asked to
+pub fn collect(
+    reachable: &Reachable,
+    paops: &mut ParseOps,
+    delete: bool,
+) -> Result<Collected, EnprotError> {
+    let (ids, url) = cas::list_written(paops)?;
+    let garbage: Vec<&String> = ids
+        .iter()
+        .filter(|id| !reachable.ids.contains(*id))
+        .collect();
+
+    // references may hide in contents that couldn't be read
+    let mut collected = Collected::default();
+    collected.refused = delete && !garbage.is_empty() && !reachable.unexamined.is_empty();
+    collected.warning = reachable.unexamined_report(if collected.refused {
+        "Not deleting anything, these contents couldn't be examined:"
+    } else {
+        "Warning: these contents couldn't be examined:"
+    });
+    for id in &garbage {
+        if delete && !collected.refused {
+            match cas::delete(id, paops) {
+                Ok(()) => collected.lines.push(format!("deleted {}", id)),
+                Err(e) => collected.errors.push(e),
+            }
+        } else {
+            collected.lines.push(format!("unreachable {}", id));
+        }
+    }
+    if !delete || collected.refused {
+        collected.lines.push(format!(
+            "{} of {} objects in {} unreachable{}",
+            garbage.len(),
+            ids.len(),
+            url,
+            if garbage.is_empty() || collected.refused {
+                ""
+            } else {
+                ", use --delete to remove them"
+            }
+        ));
+    }
+    Ok(collected)
+}
@@ -26,30 +26,56 @@ extern crate aes_gcm_siv;
 extern crate block_cipher_trait;
 extern crate botan;
 extern crate clap;
+extern crate diff;
+extern crate glob;
 extern crate hex;
 extern crate num;
 extern crate phc;
 extern crate phf;
 extern crate rpassword;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+extern crate tar;
+extern crate toml;
 
+mod atomic;
+mod bundle;
 mod cas;
 mod cipher;
+mod config;
 mod consts;
 pub mod crypto;
+mod document;
+pub mod error;
 mod etree;
+mod gc;
+mod ls;
 mod pbkdf;
 mod policy;
 mod prot;
+mod recipient;
+mod syntax;
+mod unidiff;
 pub mod utils;
+mod verify;
 
-use std::collections::BTreeMap;
+pub use cas::CasBackend;
+pub use document::{Check, CheckStatus, Document, Segment, SegmentState};
+pub use error::EnprotError;
+pub use etree::{CipherOptions, DataLines, PBKDFOptions, ParseOps, TextNode, TextTree};
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::ffi::OsString;
 use std::fs;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use clap::{App, AppSettings, Arg, ArgMatches, ArgSettings, ErrorKind, SubCommand};
 
-use clap::{App, AppSettings, Arg, ArgSettings, ErrorKind};
+use config::{Config, Separators};
 
 fn validate_positive<T>(v: String) -> Result<(), String>
 where
@@ -69,6 +95,59 @@ where
     v.parse::<T>().map_err(|_| err.clone()).map(|_| ())
 }
 
+fn validate_hex(v: String) -> Result<(), String> {
+    hex::decode(&v)
+        .map(|_| ())
+        .map_err(|_| format!("Expected a hex string, received '{}'", v))
+}
+
+fn validate_wordpass(v: String) -> Result<(), String> {
+    for val in v.split(",") {
+        let wordpass = val.splitn(2, '=').collect::<Vec<&str>>();
+        if wordpass.len() != 2 || wordpass[0].len() == 0 || wordpass[1].len() == 0 {
+            return Err(String::from(
+                "Must be of the form WORD=PASSWORD[,WORD=PASSWORD]",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_recipient(v: String) -> Result<(), String> {
+    recipient::parse_public(&v)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn validate_dir(v: String) -> Result<(), String> {
+    if Path::new(&v).is_dir() {
+        Ok(())
+    } else {
+        Err(String::from("Must be a directory"))
+    }
+}
+
+fn validate_dirs(v: String) -> Result<(), String> {
+    for dir in env::split_paths(&v) {
+        if !dir.is_dir() {
+            return Err(format!("{} must be a directory", dir.display()));
+        }
+    }
+    Ok(())
+}
+
+fn validate_pbkdf_params(v: String) -> Result<(), String> {
+    for val in v.split(",") {
+        let parts = val.splitn(2, '=').collect::<Vec<&str>>();
+        if parts.len() != 2 || parts[0].len() == 0 || parts[1].parse::<usize>().is_err() {
+            return Err(String::from(
+                "Must be of the form NAME=NUMBER[,NAME=NUMBER]",
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn err_exit(app: &mut App, desc: &str, kind: ErrorKind, show_help: bool) -> ! {
     if show_help {
         app.print_help().unwrap();
@@ -95,268 +174,344 @@ fn make_policy(app: &mut App, name: &str) -> Box<dyn crypto::CryptoPolicy> {
     }
 }
 
-// Handle command line parameters
+// options shared by all commands
 
-pub fn app_main<I, T>(args: I)
-where
-    I: IntoIterator<Item = T>,
-    T: Into<OsString> + Clone,
-{
-    // <( ENCRYPTED AUTHOR )>
-    // <( DATA X417HVMRRAs6Z1xGo5yY4TxUQ2tpAHEKQ1sg9+kfku5uUikK3y2tODtsUiGqfRGW )>
-    // <( DATA xUCGYFu02BCdqPM7uuX5UNvbfrLvKkj6gLYwg/cr42PJmr4o5xnw1qo= )>
-    // <( END AUTHOR )>
+fn common_args<'a>(default_max_depth: &'a str) -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::with_name("verbose")
+            .short("v")
+            .long("verbose")
+            .help("Produce more verbose output"),
+        Arg::with_name("quiet")
+            .short("q")
+            .long("quiet")
+            .help("Suppress unnecessary output"),
+        Arg::with_name("max-depth")
+            .long("max-depth")
+            .takes_value(true)
+            .value_name("DEPTH")
+            .validator(validate_non_negative::<usize>)
+            .default_value(default_max_depth)
+            .help("Maximum recursion depth (use 0 for infinite)"),
+        Arg::with_name("left-separator")
+            .short("l")
+            .long("left-separator")
+            .takes_value(true)
+            .value_name("SEP")
+            .default_value(consts::DEFAULT_LEFT_SEP)
+            .help("Specify left separator in parsing"),
+        Arg::with_name("right-separator")
+            .short("r")
+            .long("right-separator")
+            .takes_value(true)
+            .value_name("SEP")
+            .default_value(consts::DEFAULT_RIGHT_SEP)
+            .help("Specify right separator in parsing"),
+        Arg::with_name("syntax")
+            .long("syntax")
+            .takes_value(true)
+            .value_name("SYNTAX")
+            .possible_values(consts::VALID_SYNTAXES)
+            .help("Use the separators of SYNTAX (\"auto\" picks them by file extension)"),
+        Arg::with_name("policy")
+            .long("policy")
+            .takes_value(true)
+            .value_name("POLICY")
+            .default_value(consts::DEFAULT_POLICY)
+            .possible_values(consts::VALID_POLICIES)
+            .help("Set the policy to restrict cryptographic algorithms"),
+        Arg::with_name("defaults")
+            .long("defaults")
+            .takes_value(true)
+            .value_name("POLICY")
+            .possible_values(consts::VALID_POLICIES)
+            .help("Load settings from POLICY, but do not enforce the policy"),
+        Arg::with_name("fips").long("fips").help(
+            "Select and enforce the use of FIPS-compliant algorithms (implies --policy=nist)",
+        ),
+        Arg::with_name("casdir")
+            .short("c")
+            .long("casdir")
+            .takes_value(true)
+            .value_name("DIRECTORY")
+            .multiple(true)
+            .number_of_values(1)
+            .default_value("./")
+            .set(ArgSettings::HideDefaultValue)
+            .validator(validate_dirs)
+            .help("Directory for CAS files (default \"cas\" if exists, else \".\"); several are searched in order"),
+        Arg::with_name("write-casdir")
+            .long("write-casdir")
+            .takes_value(true)
+            .value_name("DIRECTORY")
+            .validator(validate_dir)
+            .conflicts_with("cas")
+            .help("Directory for new CAS files (default the first writable casdir)"),
+        Arg::with_name("cas")
+            .long("cas")
+            .takes_value(true)
+            .value_name("URL")
+            .conflicts_with("casdir")
+            .help("Use the CAS at URL (file:DIR, sqlite:FILE or http://HOST[:PORT]/PATH)"),
+        Arg::with_name("cas-layout")
+            .long("cas-layout")
+            .takes_value(true)
+            .value_name("LAYOUT")
+            .default_value(consts::DEFAULT_CAS_LAYOUT)
+            .possible_values(consts::VALID_CAS_LAYOUTS)
+            .help("Layout of objects in a new CAS directory"),
+        Arg::with_name("cas-hash")
+            .long("cas-hash")
+            .takes_value(true)
+            .value_name("ALG")
+            .possible_values(consts::VALID_CAS_HASH_ALGS)
+            .help("Name new CAS objects by their ALG hash (default set by policy, sha3-256)"),
+        Arg::with_name("cas-key-file")
+            .long("cas-key-file")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Name CAS objects by their HMAC under the key in FILE rather than by hash"),
+        Arg::with_name("no-config")
+            .long("no-config")
+            .help("Do not look for .enprot.toml configuration files"),
+        Arg::with_name("trust-config")
+            .long("trust-config")
+            .help("Let .enprot.toml files set a CAS, recipients, a CAS key file, a salt and an IV"),
+    ]
+}
 
-    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+// password options
 
-    let default_max_depth = consts::DEFAULT_MAX_DEPTH.to_string();
+fn key_args<'a>() -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::with_name("password")
+            .short("k")
+            .long("key")
+            .takes_value(true)
+            .value_name("WORD=PASSWORD")
+            .multiple(true)
+            .number_of_values(1)
+            .validator(validate_wordpass)
+            .help("Specify a secret PASSWORD for WORD"),
+        Arg::with_name("document-id")
+            .long("document-id")
+            .takes_value(true)
+            .value_name("ID")
+            .help("Bind new ciphertexts to document ID, which is needed to decrypt them"),
+        Arg::with_name("identity")
+            .short("i")
+            .long("identity")
+            .takes_value(true)
+            .value_name("FILE")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Decrypt segments encrypted to a recipient key with its secret in FILE"),
+    ]
+}
 
-    let mut app = App::new("enprot")
-        .version(VERSION)
-        .setting(AppSettings::DeriveDisplayOrder)
-        .setting(AppSettings::ColoredHelp)
-        .setting(AppSettings::ColorAuto)
-        .arg(
-            Arg::with_name("verbose")
-                .short("v")
-                .long("verbose")
-                .help("Produce more verbose output"),
-        )
-        .arg(
-            Arg::with_name("quiet")
-                .short("q")
-                .long("quiet")
-                .help("Suppress unnecessary output"),
-        )
-        .arg(
-            Arg::with_name("max-depth")
-                .long("max-depth")
-                .takes_value(true)
-                .value_name("DEPTH")
-                .validator(validate_non_negative::<usize>)
-                .default_value(&default_max_depth)
-                .help("Maximum recursion depth (use 0 for infinite)"),
-        )
-        .arg(
-            Arg::with_name("left-separator")
-                .short("l")
-                .long("left-separator")
-                .takes_value(true)
-                .value_name("SEP")
-                .default_value(consts::DEFAULT_LEFT_SEP)
-                .help("Specify left separator in parsing"),
-        )
-        .arg(
-            Arg::with_name("right-separator")
-                .short("r")
-                .long("right-separator")
-                .takes_value(true)
-                .value_name("SEP")
-                .default_value(consts::DEFAULT_RIGHT_SEP)
-                .help("Specify right separator in parsing"),
-        )
-        .arg(
-            Arg::with_name("store")
-                .short("s")
-                .long("store")
-                .takes_value(true)
-                .value_name("WORD")
-                .multiple(true)
-                .number_of_values(1)
-                .help("Store (unencrypted) WORD segments to CAS"),
-        )
-        .arg(
-            Arg::with_name("fetch")
-                .short("f")
-                .long("fetch")
-                .takes_value(true)
-                .value_name("WORD")
-                .multiple(true)
-                .number_of_values(1)
-                .help("Fetch (unencrypted) WORD segments to CAS"),
-        )
-        .arg(
-            Arg::with_name("password")
-                .short("k")
-                .long("key")
-                .takes_value(true)
-                .value_name("WORD=PASSWORD")
-                .multiple(true)
-                .number_of_values(1)
-                .validator(|v: String| -> Result<(), String> {
-                    for val in v.split(",") {
-                        let wordpass = val.splitn(2, '=').collect::<Vec<&str>>();
-                        if wordpass.len() != 2 || wordpass[0].len() == 0 || wordpass[1].len() == 0 {
-                            return Err(String::from(
-                                "Must be of the form WORD=PASSWORD[,WORD=PASSWORD]",
-                            ));
-                        }
-                    }
-                    Ok(())
-                })
-                .help("Specify a secret PASSWORD for WORD"),
-        )
-        .arg(
-            Arg::with_name("encrypt")
-                .short("e")
-                .long("encrypt")
-                .takes_value(true)
-                .value_name("WORD")
-                .multiple(true)
-                .number_of_values(1)
-                .help("Encrypt WORD segments"),
-        )
-        .arg(
-            Arg::with_name("encrypt-store")
-                .short("E")
-                .long("encrypt-store")
-                .takes_value(true)
-                .value_name("WORD")
-                .multiple(true)
-                .number_of_values(1)
-                .help("Encrypt and store WORD segments"),
-        )
-        .arg(
-            Arg::with_name("policy")
-                .long("policy")
-                .takes_value(true)
-                .value_name("POLICY")
-                .default_value(consts::DEFAULT_POLICY)
-                .possible_values(consts::VALID_POLICIES)
-                .help("Set the policy to restrict cryptographic algorithms"),
-        )
-        .arg(
-            Arg::with_name("defaults")
-                .long("defaults")
-                .takes_value(true)
-                .value_name("POLICY")
-                .possible_values(consts::VALID_POLICIES)
-                .help("Load settings from POLICY, but do not enforce the policy"),
-        )
-        .arg(Arg::with_name("fips").long("fips").help(
-            "Select and enforce the use of FIPS-compliant algorithms (implies --policy=nist)",
-        ))
-        .arg(
-            Arg::with_name("pbkdf")
-                .long("pbkdf")
-                .takes_value(true)
-                .value_name("ALG")
-                .possible_values(consts::VALID_PBKDF_ALGS)
-                .help("Set the PBKDF algorithm to use when encrypting"),
-        )
-        .arg(
-            Arg::with_name("pbkdf-msec")
-                .long("pbkdf-msec")
-                .takes_value(true)
-                .value_name("MSEC")
-                .validator(validate_positive::<u32>)
-                .help("Set the millisecond count for the PBKDF algorithm"),
-        )
-        .arg(
-            Arg::with_name("pbkdf-salt-len")
-                .long("pbkdf-salt-len")
-                .takes_value(true)
-                .value_name("BYTES")
-                .validator(&validate_positive::<usize>)
-                .help("Set the salt length for the PBKDF"),
-        )
-        .arg(
-            Arg::with_name("pbkdf-params")
-                .long("pbkdf-params")
-                .takes_value(true)
-                .value_name("PARAMS")
-                .hidden(true)
-                .help("Advanced option for testing, do not use"),
-        )
-        .arg(
-            Arg::with_name("pbkdf-salt")
-                .long("pbkdf-salt")
-                .takes_value(true)
-                .value_name("HEX")
-                .hidden(true)
-                .help("Advanced option for testing, do not use"),
-        )
-        .arg(
-            Arg::with_name("pbkdf-disable-cache")
-                .long("pbkdf-disable-cache")
-                .help("Disable the PBKDF cache mechanism"),
-        )
-        .arg(
-            Arg::with_name("cipher")
-                .long("cipher")
-                .takes_value(true)
-                .value_name("ALG")
-                .possible_values(consts::VALID_CIPHER_ALGS)
-                .help("Set the cipher algorithm to use when encrypting"),
-        )
-        .arg(
-            Arg::with_name("cipher-iv")
-                .long("cipher-iv")
-                .takes_value(true)
-                .value_name("ALG")
-                .hidden(true)
-                .help("Advanced option for testing, do not use"),
-        )
-        .arg(
-            Arg::with_name("decrypt")
-                .short("d")
-                .long("decrypt")
-                .takes_value(true)
-                .value_name("WORD")
-                .multiple(true)
-                .number_of_values(1)
-                .help("Decrypt WORD segments"),
-        )
-        .arg(
-            Arg::with_name("casdir")
-                .short("c")
-                .long("casdir")
-                .takes_value(true)
-                .value_name("DIRECTORY")
-                .default_value("./")
-                .set(ArgSettings::HideDefaultValue)
-                .validator(|v: String| -> Result<(), String> {
-                    if Path::new(&v).is_dir() {
-                        return Ok(());
-                    } else {
-                        Err(String::from("Must be a directory"))
-                    }
-                })
-                .help("Directory for CAS files (default \"cas\" if exists, else \".\")"),
-        )
-        .arg(
-            Arg::with_name("prefix")
-                .short("p")
-                .long("prefix")
-                .takes_value(true)
-                .value_name("PREFIX")
-                .default_value("")
-                .set(ArgSettings::HideDefaultValue)
-                .set(ArgSettings::EmptyValues)
-                .help("Use PREFIX for output filenames"),
-        )
-        .arg(
-            Arg::with_name("output")
-                .short("o")
-                .long("output")
-                .takes_value(true)
-                .value_name("FILE")
-                .multiple(true)
-                .number_of_values(1)
-                .help("Specify output file for previous input"),
-        )
-        .arg(
-            Arg::with_name("input")
-                .required(true)
-                .index(1)
-                .value_name("FILE")
-                .default_value("-")
-                .multiple(true)
-                .help("The input file(s)"),
-        );
-    let matches = app.clone().get_matches_from(args);
+// options controlling how new ciphertexts are made
+
+fn crypto_args<'a>() -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::with_name("pbkdf")
+            .long("pbkdf")
+            .takes_value(true)
+            .value_name("ALG")
+            .possible_values(consts::VALID_PBKDF_ALGS)
+            .help("Set the PBKDF algorithm to use when encrypting"),
+        Arg::with_name("pbkdf-msec")
+            .long("pbkdf-msec")
+            .takes_value(true)
+            .value_name("MSEC")
+            .validator(validate_positive::<u32>)
+            .help("Set the millisecond count for the PBKDF algorithm"),
+        Arg::with_name("pbkdf-salt-len")
+            .long("pbkdf-salt-len")
+            .takes_value(true)
+            .value_name("BYTES")
+            .validator(&validate_positive::<usize>)
+            .help("Set the salt length for the PBKDF"),
+        Arg::with_name("pbkdf-params")
+            .long("pbkdf-params")
+            .takes_value(true)
+            .value_name("PARAMS")
+            .validator(validate_pbkdf_params)
+            .hidden(true)
+            .help("Advanced option for testing, do not use"),
+        Arg::with_name("pbkdf-salt")
+            .long("pbkdf-salt")
+            .takes_value(true)
+            .value_name("HEX")
+            .validator(validate_hex)
+            .hidden(true)
+            .help("Advanced option for testing, do not use"),
+        Arg::with_name("pbkdf-disable-cache")
+            .long("pbkdf-disable-cache")
+            .help("Disable the PBKDF cache mechanism"),
+        Arg::with_name("cipher")
+            .long("cipher")
+            .takes_value(true)
+            .value_name("ALG")
+            .possible_values(consts::VALID_CIPHER_ALGS)
+            .help("Set the cipher algorithm to use when encrypting"),
+        Arg::with_name("recipient")
+            .long("recipient")
+            .takes_value(true)
+            .value_name("PUBKEY")
+            .multiple(true)
+            .number_of_values(1)
+            .validator(validate_recipient)
+            .help("Encrypt to the x25519:... public key PUBKEY, besides or instead of a password"),
+        Arg::with_name("cipher-iv")
+            .long("cipher-iv")
+            .takes_value(true)
+            .value_name("ALG")
+            .validator(validate_hex)
+            .hidden(true)
+            .help("Advanced option for testing, do not use"),
+        Arg::with_name("data-key")
+            .long("data-key")
+            .takes_value(true)
+            .value_name("HEX")
+            .validator(validate_hex)
+            .hidden(true)
+            .help("Advanced option for testing, do not use"),
+    ]
+}
+
+// output options of commands that rewrite their inputs
+
+fn output_args<'a>() -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::with_name("prefix")
+            .short("p")
+            .long("prefix")
+            .takes_value(true)
+            .value_name("PREFIX")
+            .default_value("")
+            .set(ArgSettings::HideDefaultValue)
+            .set(ArgSettings::EmptyValues)
+            .help("Use PREFIX for output filenames"),
+        Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .takes_value(true)
+            .value_name("FILE")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Specify output file for previous input"),
+        Arg::with_name("backup")
+            .long("backup")
+            .takes_value(true)
+            .value_name("SUFFIX")
+            .min_values(0)
+            .require_equals(true)
+            .help("Keep the original of each rewritten file, with SUFFIX (default \"~\") appended"),
+        Arg::with_name("dry-run")
+            .long("dry-run")
+            .help("Print the changes as a unified diff instead of writing anything"),
+    ]
+}
+
+// the new password of commands that give one
+
+fn new_key_arg<'a>(help: &'a str) -> Arg<'a, 'a> {
+    Arg::with_name("new-key")
+        .short("n")
+        .long("new-key")
+        .takes_value(true)
+        .value_name("WORD=PASSWORD")
+        .multiple(true)
+        .number_of_values(1)
+        .validator(validate_wordpass)
+        .help(help)
+}
+
+// the keyword list argument of subcommands
+
+fn words_arg<'a>(help: &'a str) -> Arg<'a, 'a> {
+    Arg::with_name("words")
+        .required(true)
+        .value_name("WORD[,WORD...]")
+        .help(help)
+}
+
+// the input files
+
+fn input_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("input")
+        .value_name("FILE")
+        .default_value("-")
+        .multiple(true)
+        .help("The input file(s)")
+}
+
+// print the notes the library left while working, which it only leaves when
+// verbose
+
+fn print_notes(paops: &mut ParseOps) {
+    for note in paops.notes.drain(..) {
+        eprintln!("{}", note);
+    }
+}
+
+// a command line value, if given explicitly
+
+fn explicit<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    if matches.occurrences_of(name) != 0 {
+        matches.value_of(name)
+    } else {
+        None
+    }
+}
+
+// look up the configuration applying to an input file
+
+fn find_config(
+    cache: &mut HashMap<PathBuf, Option<Rc<Config>>>,
+    file: &str,
+    trusted: bool,
+) -> Result<Option<Rc<Config>>, EnprotError> {
+    let dir = match Path::new(file).parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let dir = match fs::canonicalize(dir) {
+        Ok(dir) => dir,
+        Err(_) => return Ok(None),
+    };
+    if let Some(config) = cache.get(&dir) {
+        return Ok(config.clone());
+    }
+    let config = Config::discover(&dir, trusted)?.map(Rc::new);
+    cache.insert(dir, config.clone());
+    Ok(config)
+}
+
+// the CAS key is the contents of a file, less any trailing newline
+
+fn read_cas_key(path: &Path) -> Result<Vec<u8>, EnprotError> {
+    let mut key = fs::read(path).map_err(|io| EnprotError::Io {
+        path: path.display().to_string(),
+        io,
+    })?;
+    while key.last().is_some_and(|c| c.is_ascii_whitespace()) {
+        key.pop();
+    }
+    if key.len() < consts::CAS_KEY_MIN_LEN {
+        return Err(EnprotError::Crypto("CAS key must be at least 16 bytes"));
+    }
+    Ok(key)
+}
 
-    let mut policy = matches.value_of("policy").unwrap();
+// combine the defaults, the configuration and the command line
+
+fn make_paops(app: &mut App, cmd: &str, matches: &ArgMatches, config: Option<&Config>) -> ParseOps {
+    let mut policy = explicit(matches, "policy")
+        .or(config.and_then(|c| c.policy.as_deref()))
+        .unwrap_or(consts::DEFAULT_POLICY);
+    let explicit_policy =
+        matches.occurrences_of("policy") != 0 || config.is_some_and(|c| c.policy.is_some());
     // check if fips mode is requested (implicitly or explicitly)
     let fips = matches.occurrences_of("fips") != 0
         || (cfg!(unix)
@@ -366,9 +521,9 @@ where
             });
     if fips {
         // check if the user specified a conflicting policy
-        if matches.occurrences_of("policy") != 0 && policy != "nist" {
+        if explicit_policy && policy != "nist" {
             err_exit(
-                &mut app,
+                app,
                 &format!("Policy setting of '{}' conflicts with --fips", policy),
                 ErrorKind::ArgumentConflict,
                 false,
@@ -379,59 +534,166 @@ where
     }
     assert!(!fips || (fips && policy == "nist"));
     // instantiate the actual policy
-    let policy = make_policy(&mut app, policy);
+    let policy = make_policy(app, policy);
 
     // the policy will set default crypto-related values
     let mut paops;
-    if let Some(defaults) = matches.value_of("defaults") {
-        paops = etree::ParseOps::new(make_policy(&mut app, defaults));
+    let defaults = explicit(matches, "defaults").or(config.and_then(|c| c.defaults.as_deref()));
+    if let Some(defaults) = defaults {
+        paops = etree::ParseOps::new(make_policy(app, defaults));
         paops.policy = policy;
     } else {
         paops = etree::ParseOps::new(policy);
     }
 
-    // casdir
-    if matches.occurrences_of("casdir") == 0 && Path::new("cas").is_dir() {
-        paops.casdir = Path::new("cas").to_path_buf();
-    } else {
-        paops.casdir = Path::new(matches.value_of("casdir").unwrap()).to_path_buf();
-    }
     // verbosity
-    paops.verbose = matches.occurrences_of("verbose") != 0;
+    paops.verbose =
+        matches.occurrences_of("verbose") != 0 || config.and_then(|c| c.verbose).unwrap_or(false);
     if matches.occurrences_of("quiet") != 0 {
         paops.verbose = false;
     }
+    paops.dry_run = matches.occurrences_of("dry-run") != 0;
+    // casdirs, searched in order
+    let mut casdirs: Vec<PathBuf> = if explicit(matches, "casdir").is_some() {
+        matches
+            .values_of("casdir")
+            .unwrap()
+            .flat_map(|dirs| env::split_paths(dirs).collect::<Vec<PathBuf>>())
+            .collect()
+    } else if let Some(casdirs) = config.and_then(|c| c.casdir.clone()) {
+        casdirs
+    } else if Path::new("cas").is_dir() {
+        vec![Path::new("cas").to_path_buf()]
+    } else {
+        vec![Path::new(matches.value_of("casdir").unwrap()).to_path_buf()]
+    };
+    let write_casdir = match explicit(matches, "write-casdir") {
+        Some(dir) => Some(Path::new(dir).to_path_buf()),
+        None => config.and_then(|c| c.write_casdir.clone()),
+    };
+    // the designated casdir for writes is searched first if not listed
+    let write = write_casdir.map(|dir| match casdirs.iter().position(|d| *d == dir) {
+        Some(i) => i,
+        None => {
+            casdirs.insert(0, dir);
+            0
+        }
+    });
+    paops.casdir = casdirs[write.unwrap_or(0)].clone();
+    paops.cas_layout = match explicit(matches, "cas-layout") {
+        Some(layout) => layout.to_string(),
+        None => config
+            .and_then(|c| c.cas_layout.clone())
+            .unwrap_or_else(|| consts::DEFAULT_CAS_LAYOUT.to_string()),
+    };
+    if casdirs.len() > 1 {
+        let stores = casdirs
+            .into_iter()
+            .map(|dir| {
+                Box::new(cas::DirBackend::new(dir, &paops.cas_layout)) as Box<dyn CasBackend>
+            })
+            .collect();
+        paops.cas = Some(Box::new(cas::SearchPath::new(stores, write)));
+    }
+    // other cas backends
+    let cas = match explicit(matches, "cas") {
+        Some(url) => Some((url, Path::new("."))),
+        None if explicit(matches, "casdir").is_some()
+            || explicit(matches, "write-casdir").is_some() =>
+        {
+            None
+        }
+        None => config.and_then(|c| {
+            c.cas
+                .as_ref()
+                .map(|url| (url.as_str(), c.path.parent().unwrap_or(Path::new("."))))
+        }),
+    };
+    if let Some((url, base)) = cas {
+        match cas::open(url, base, &paops.cas_layout) {
+            Ok(backend) => paops.cas = Some(backend),
+            Err(e) => {
+                eprintln!("{}, aborting.", e);
+                ::std::process::exit(1);
+            }
+        }
+    }
+    // hash for naming new cas objects
+    if let Some(alg) = explicit(matches, "cas-hash").or(config.and_then(|c| c.cas_hash.as_deref()))
+    {
+        paops.cas_hash = alg.to_string();
+    }
+    // key for naming cas objects
+    let cas_key_file = match explicit(matches, "cas-key-file") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => config.and_then(|c| c.cas_key_file.clone()),
+    };
+    if let Some(path) = cas_key_file {
+        match read_cas_key(&path) {
+            Ok(key) => paops.cas_key = Some(key),
+            Err(e) => {
+                eprintln!("{}, aborting.", e);
+                ::std::process::exit(1);
+            }
+        }
+    }
     // max recursion depth
-    paops.max_depth = matches
-        .value_of("max-depth")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    // separators
-    paops.left_sep = matches.value_of("left-separator").unwrap().to_string();
-    paops.right_sep = matches.value_of("right-separator").unwrap().to_string();
-    // transforms arguments like ["a", "b,c", "d"] into ["a", "b", "c", "d"]
+    paops.max_depth = match explicit(matches, "max-depth") {
+        Some(depth) => depth.parse::<usize>().unwrap(),
+        None => config
+            .and_then(|c| c.max_depth)
+            .unwrap_or(consts::DEFAULT_MAX_DEPTH),
+    };
+    // transforms arguments like ["a", "b,c", "d"] into ["a", "b", "c", "d"],
+    // falling back to the configuration if not given
     macro_rules! csep_arg {
-        ( $set:expr, $name:expr ) => {
-            $set.extend(
-                matches
-                    .values_of($name)
-                    .unwrap_or(clap::Values::default())
-                    .flat_map(|arg| arg.split(",").map(|val| val.to_string()))
-                    .collect::<Vec<String>>(),
-            );
+        ( $set:expr, $name:expr, $field:ident ) => {
+            if matches.occurrences_of($name) != 0 {
+                $set.extend(
+                    matches
+                        .values_of($name)
+                        .unwrap_or(clap::Values::default())
+                        .flat_map(|arg| arg.split(",").map(|val| val.to_string()))
+                        .collect::<Vec<String>>(),
+                );
+            } else if let Some(list) = config.and_then(|c| c.$field.as_ref()) {
+                $set.extend(list.iter().cloned());
+            }
         };
     }
-    // expand comma-separated args
-    csep_arg!(paops.store, "store");
-    csep_arg!(paops.fetch, "fetch");
-    csep_arg!(paops.encrypt, "encrypt");
-    csep_arg!(paops.encrypt, "encrypt-store");
-    csep_arg!(paops.store, "encrypt-store");
-    csep_arg!(paops.decrypt, "decrypt");
+    if cmd.is_empty() {
+        // expand comma-separated args
+        csep_arg!(paops.store, "store", store);
+        csep_arg!(paops.fetch, "fetch", fetch);
+        csep_arg!(paops.encrypt, "encrypt", encrypt);
+        csep_arg!(paops.encrypt, "encrypt-store", encrypt_store);
+        csep_arg!(paops.store, "encrypt-store", encrypt_store);
+        csep_arg!(paops.decrypt, "decrypt", decrypt);
+    } else if let Some(words) = matches.value_of("words") {
+        // the subcommand alone determines the operation
+        let words = words.split(",").map(|word| word.to_string());
+        match cmd {
+            "encrypt" => {
+                if matches.occurrences_of("store") != 0 {
+                    paops.store.extend(words.clone());
+                }
+                paops.encrypt.extend(words);
+            }
+            "decrypt" => paops.decrypt.extend(words),
+            "store" => paops.store.extend(words),
+            "fetch" => paops.fetch.extend(words),
+            "rekey" => paops.rekey.extend(words),
+            "access-add" => paops.access_add.extend(words),
+            "access-remove" => paops.access_remove.extend(words),
+            _ => unreachable!(),
+        }
+    }
     // password
     // ["word1=pass1", "word2=pass2,word3=pass3"] ->
     //   [(word1, pass1), (word2, pass2), (word3, pass3)]
+    if let Some(config) = config {
+        paops.passwords.extend(config.passwords.clone());
+    }
     paops.passwords.extend(
         matches
             .values_of("password")
@@ -444,6 +706,78 @@ where
             }),
     );
 
+    // recipient public keys and the identities to decrypt with
+    match matches.values_of("recipient") {
+        Some(keys) => {
+            paops.recipients = keys
+                .map(|key| recipient::parse_public(key).unwrap())
+                .collect()
+        }
+        None => {
+            if let Some(keys) = config.and_then(|c| c.recipients.clone()) {
+                paops.recipients = keys;
+            }
+        }
+    }
+    for path in matches
+        .values_of("identity")
+        .unwrap_or(clap::Values::default())
+    {
+        match recipient::read_identities(Path::new(path)) {
+            Ok(secrets) => paops.identities.extend(secrets),
+            Err(e) => {
+                eprintln!("{}, aborting.", e);
+                ::std::process::exit(1);
+            }
+        }
+    }
+
+    // document id authenticated along with new ciphertexts
+    paops.document_id = explicit(matches, "document-id")
+        .map(|id| id.to_string())
+        .or(config.and_then(|c| c.document_id.clone()));
+
+    paops.new_passwords.extend(
+        matches
+            .values_of("new-key")
+            .unwrap_or(clap::Values::default())
+            .flat_map(|arg| {
+                arg.split(",").map(|val| {
+                    let wordpass = val.splitn(2, '=').collect::<Vec<&str>>();
+                    (wordpass[0].to_string(), wordpass[1].to_string())
+                })
+            }),
+    );
+
+    // configured crypto settings, overridden below by the command line
+    if let Some(config) = config {
+        if let Some(ref pbkdf) = config.pbkdf {
+            paops.pbkdfopts.alg = pbkdf.clone();
+        }
+        if let Some(saltlen) = config.pbkdf_salt_len {
+            paops.pbkdfopts.saltlen = saltlen;
+        }
+        if let Some(msec) = config.pbkdf_msec {
+            paops.pbkdfopts.msec = Some(msec);
+        }
+        if let Some(ref params) = config.pbkdf_params {
+            paops.pbkdfopts.msec = None;
+            paops.pbkdfopts.params = Some(params.clone());
+        }
+        if let Some(ref salt) = config.pbkdf_salt {
+            paops.pbkdfopts.salt = Some(salt.clone());
+        }
+        if config.pbkdf_disable_cache == Some(true) {
+            paops.pbkdf_cache = None;
+        }
+        if let Some(ref cipher) = config.cipher {
+            paops.cipheropts.alg = cipher.clone();
+        }
+        if let Some(ref iv) = config.cipher_iv {
+            paops.cipheropts.iv = Some(iv.clone());
+        }
+    }
+
     // pbkdf
     if let Some(pbkdf) = matches.value_of("pbkdf") {
         paops.pbkdfopts.alg = pbkdf.to_string();
@@ -453,6 +787,7 @@ where
     }
     if let Some(msec) = matches.value_of("pbkdf-msec") {
         paops.pbkdfopts.msec = Some(msec.parse::<u32>().unwrap());
+        paops.pbkdfopts.params = None;
     }
     if let Some(val) = matches.value_of("pbkdf-params") {
         paops.pbkdfopts.msec = None;
@@ -476,24 +811,469 @@ where
     if let Some(iv) = matches.value_of("cipher-iv") {
         paops.cipheropts.iv = Some(hex::decode(iv).unwrap());
     }
+    if let Some(key) = matches.value_of("data-key") {
+        paops.cipheropts.key = Some(hex::decode(key).unwrap());
+    }
+
+    paops
+}
+
+// Handle command line parameters
+
+// the configuration applying to the current directory, unless disabled
+
+fn local_config(matches: &ArgMatches) -> Option<Rc<Config>> {
+    let trusted = matches.occurrences_of("trust-config") != 0;
+    if matches.occurrences_of("no-config") != 0 {
+        return None;
+    }
+    match find_config(&mut HashMap::new(), ".", trusted) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}, aborting.", e);
+            ::std::process::exit(1);
+        }
+    }
+}
+
+// settings for commands on the CAS given by the options, or else by the
+// configuration of the current directory
+
+fn local_paops(app: &mut App, cmd: &str, matches: &ArgMatches) -> ParseOps {
+    let config = local_config(matches);
+    make_paops(app, cmd, matches, config.as_ref().map(|c| c.as_ref()))
+}
+
+fn fsck_main(app: &mut App, matches: &ArgMatches) {
+    let config = local_config(matches);
+    let paops = make_paops(app, "fsck", matches, config.as_ref().map(|c| c.as_ref()));
+    let quarantine = matches.occurrences_of("quarantine") != 0;
+
+    let dirs = match paops.cas {
+        Some(ref backend) => backend.dirs(),
+        None => vec![paops.casdir.clone()],
+    };
+    // a directory only picked by default must be marked as a casdir, lest
+    // whatever happens to be in the current directory is taken for one
+    let configured =
+        config.is_some_and(|c| c.casdir.is_some() || c.write_casdir.is_some() || c.cas.is_some());
+    if !configured && explicit(matches, "casdir").is_none() && explicit(matches, "cas").is_none() {
+        for dir in &dirs {
+            if !dir.join(consts::CAS_MARKER).is_file() {
+                eprintln!(
+                    "{} has no {} marker, name it with -c to check it anyway, aborting.",
+                    dir.display(),
+                    consts::CAS_MARKER
+                );
+                ::std::process::exit(1);
+            }
+        }
+    }
+    let reports = if dirs.is_empty() {
+        let backend = paops.cas.as_ref().unwrap();
+        if quarantine {
+            eprintln!("Can't quarantine objects of {}, aborting.", backend.url());
+            ::std::process::exit(1);
+        }
+        vec![(
+            backend.url(),
+            cas::fsck::check_store(backend.as_ref(), &paops),
+        )]
+    } else {
+        dirs.iter()
+            .map(|dir| {
+                let url = dir.display().to_string();
+                (url, cas::fsck::check_dir(dir, &paops, quarantine))
+            })
+            .collect()
+    };
 
-    // print some of the processing parameters if verbose
-    if paops.verbose {
-        eprintln!(
-            "LEFT_SEP='{}' RIGHT_SEP='{}' casdir = '{}'",
-            paops.left_sep,
-            paops.right_sep,
-            paops.casdir.display(),
+    let mut clean = true;
+    for (url, report) in reports {
+        let report = match report {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("{}, aborting.", e);
+                ::std::process::exit(1);
+            }
+        };
+        for finding in &report.findings {
+            match finding.moved_to {
+                Some(ref dest) => println!(
+                    "{}: {}, moved to {}",
+                    finding.path,
+                    finding.problem,
+                    dest.display()
+                ),
+                None => println!("{}: {}", finding.path, finding.problem),
+            }
+        }
+        if paops.verbose || !report.findings.is_empty() {
+            eprintln!(
+                "{}: {} objects checked, {} problems",
+                url,
+                report.checked,
+                report.findings.len()
+            );
+        }
+        clean = clean && report.clean();
+    }
+    if !clean {
+        ::std::process::exit(1);
+    }
+}
+
+// generate a recipient identity, printing it or writing it to a file of its own
+
+fn keygen_main(matches: &ArgMatches) {
+    let generated = botan::RandomNumberGenerator::new()
+        .map_err(|_| EnprotError::Crypto("Botan error creating RNG"))
+        .and_then(|rng| recipient::generate(&rng));
+    let (identity, public) = match generated {
+        Ok(generated) => generated,
+        Err(e) => {
+            eprintln!("{}, aborting.", e);
+            ::std::process::exit(1);
+        }
+    };
+    // the public key is also in a comment of the identity
+    match matches.value_of("output") {
+        Some(path) => {
+            if let Err(e) = recipient::write_identity(Path::new(path), &identity) {
+                eprintln!("{}, aborting.", e);
+                ::std::process::exit(1);
+            }
+            println!("{}", public);
+        }
+        None => print!("{}", identity),
+    }
+}
+
+// verify bundles completely, then add their objects to the CAS
+
+fn import_main(app: &mut App, matches: &ArgMatches) {
+    let mut paops = local_paops(app, "import", matches);
+    for path in matches.values_of("bundle").unwrap() {
+        let read = if path == "-" {
+            bundle::Bundle::read(std::io::stdin(), "<stdin>", &paops)
+        } else {
+            File::open(path)
+                .map_err(|io| EnprotError::Io {
+                    path: path.to_string(),
+                    io,
+                })
+                .and_then(|file| bundle::Bundle::read(file, path, &paops))
+        };
+        let bundle = match read {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                eprintln!("{}, aborting.", e);
+                ::std::process::exit(1);
+            }
+        };
+        let count = bundle.objects.len();
+        for (id, blob) in bundle.objects {
+            let saved = cas::save_as(&id, blob, &mut paops);
+            print_notes(&mut paops);
+            if let Err(e) = saved {
+                eprintln!("{}, aborting.", e);
+                ::std::process::exit(1);
+            }
+        }
+        println!(
+            "{}: {} objects for {} imported into {}",
+            path,
+            count,
+            bundle.documents.join(", "),
+            cas::url(&paops)
         );
     }
+}
+
+pub fn app_main<I, T>(args: I)
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    // <( ENCRYPTED AUTHOR )>
+    // <( DATA X417HVMRRAs6Z1xGo5yY4TxUQ2tpAHEKQ1sg9+kfku5uUikK3y2tODtsUiGqfRGW )>
+    // <( DATA xUCGYFu02BCdqPM7uuX5UNvbfrLvKkj6gLYwg/cr42PJmr4o5xnw1qo= )>
+    // <( END AUTHOR )>
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    let default_max_depth = consts::DEFAULT_MAX_DEPTH.to_string();
+
+    let mut app = App::new("enprot")
+        .version(VERSION)
+        .global_setting(AppSettings::DeriveDisplayOrder)
+        .global_setting(AppSettings::ColoredHelp)
+        .global_setting(AppSettings::ColorAuto)
+        .setting(AppSettings::VersionlessSubcommands)
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .args(&common_args(&default_max_depth))
+        // the operations of the flag form, kept for compatibility
+        .args(&[
+            Arg::with_name("store")
+                .short("s")
+                .long("store")
+                .takes_value(true)
+                .value_name("WORD")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Store (unencrypted) WORD segments to CAS"),
+            Arg::with_name("fetch")
+                .short("f")
+                .long("fetch")
+                .takes_value(true)
+                .value_name("WORD")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Fetch (unencrypted) WORD segments to CAS"),
+            Arg::with_name("encrypt")
+                .short("e")
+                .long("encrypt")
+                .takes_value(true)
+                .value_name("WORD")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Encrypt WORD segments"),
+            Arg::with_name("encrypt-store")
+                .short("E")
+                .long("encrypt-store")
+                .takes_value(true)
+                .value_name("WORD")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Encrypt and store WORD segments"),
+            Arg::with_name("decrypt")
+                .short("d")
+                .long("decrypt")
+                .takes_value(true)
+                .value_name("WORD")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Decrypt WORD segments"),
+        ])
+        .args(&key_args())
+        .args(&crypto_args())
+        .args(&output_args())
+        .arg(input_arg())
+        .subcommand(
+            SubCommand::with_name("encrypt")
+                .about("Encrypt WORD segments")
+                .arg(words_arg("The keywords of the segments to encrypt"))
+                .arg(
+                    Arg::with_name("store")
+                        .short("s")
+                        .long("store")
+                        .help("Also store the ciphertexts to CAS"),
+                )
+                .args(&key_args())
+                .args(&crypto_args())
+                .args(&common_args(&default_max_depth))
+                .args(&output_args())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("decrypt")
+                .about("Decrypt WORD segments")
+                .arg(words_arg("The keywords of the segments to decrypt"))
+                .args(&key_args())
+                .args(&common_args(&default_max_depth))
+                .args(&output_args())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("store")
+                .about("Store (unencrypted) WORD segments to CAS")
+                .arg(words_arg("The keywords of the segments to store"))
+                .args(&common_args(&default_max_depth))
+                .args(&output_args())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("fetch")
+                .about("Fetch WORD segments from CAS")
+                .arg(words_arg("The keywords of the segments to fetch"))
+                .args(&common_args(&default_max_depth))
+                .args(&output_args())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("ls")
+                .about("List the segments of documents without modifying them")
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Produce the listing in JSON"),
+                )
+                .args(&common_args(&default_max_depth))
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Check CAS references and authenticate ciphertexts without writing")
+                .args(&key_args())
+                .args(&common_args(&default_max_depth))
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("gc")
+                .about("List or delete CAS objects not referenced by the given documents")
+                .arg(
+                    Arg::with_name("delete")
+                        .long("delete")
+                        .help("Delete the unreferenced objects instead of listing them"),
+                )
+                .args(&key_args())
+                .args(&common_args(&default_max_depth))
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("cas")
+                .about("Maintain the CAS itself")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("fsck")
+                        .about("Check every CAS object against its name")
+                        .arg(
+                            Arg::with_name("quarantine")
+                                .long("quarantine")
+                                .help("Move bad objects and stray files to lost+found"),
+                        )
+                        .args(&common_args(&default_max_depth)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bundle")
+                .about("Pass CAS objects on to others")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("export")
+                        .about("Collect the CAS objects documents refer to into a bundle")
+                        .arg(
+                            Arg::with_name("bundle")
+                                .short("o")
+                                .long("output")
+                                .takes_value(true)
+                                .value_name("BUNDLE")
+                                .required(true)
+                                .help("Write the bundle to BUNDLE, or - for stdout"),
+                        )
+                        .args(&key_args())
+                        .args(&common_args(&default_max_depth))
+                        .arg(input_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("import")
+                        .about("Verify the objects of bundles and add them to the CAS")
+                        .args(&common_args(&default_max_depth))
+                        .arg(
+                            Arg::with_name("bundle")
+                                .value_name("BUNDLE")
+                                .required(true)
+                                .multiple(true)
+                                .help("Bundles to import, or - for stdin"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("keygen")
+                .about("Create an identity to receive segments encrypted with --recipient")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Write the identity to the new FILE and print its public key"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rekey")
+                .about("Replace the password of WORD segments")
+                .arg(words_arg("The keywords of the segments to rekey"))
+                .args(&key_args())
+                .arg(new_key_arg("Specify the new PASSWORD for WORD"))
+                .args(&crypto_args())
+                .args(&common_args(&default_max_depth))
+                .args(&output_args())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("access")
+                .about("Change the passwords of WORD segments without re-encrypting them")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Let another password decrypt WORD segments")
+                        .arg(words_arg(
+                            "The keywords of the segments to give the password",
+                        ))
+                        .args(&key_args())
+                        .arg(new_key_arg("Specify the added PASSWORD for WORD"))
+                        .args(&crypto_args())
+                        .args(&common_args(&default_max_depth))
+                        .args(&output_args())
+                        .arg(input_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("remove")
+                        .about("Drop the password given with -k from WORD segments")
+                        .arg(words_arg(
+                            "The keywords of the segments to take the password from",
+                        ))
+                        .args(&key_args())
+                        .args(&common_args(&default_max_depth))
+                        .args(&output_args())
+                        .arg(input_arg()),
+                ),
+        );
+    let all_matches = app.clone().get_matches_from(args);
+    // the flag form is the top level command itself
+    let (cmd, matches) = match all_matches.subcommand() {
+        (name, Some(sub_matches)) => (name, sub_matches),
+        _ => ("", &all_matches),
+    };
+
+    // commands on the CAS rather than on documents
+    match (cmd, matches.subcommand()) {
+        ("cas", ("fsck", Some(matches))) => return fsck_main(&mut app, matches),
+        ("bundle", ("import", Some(matches))) => return import_main(&mut app, matches),
+        ("keygen", _) => return keygen_main(matches),
+        _ => (),
+    }
+    // bundle export and access read documents like the other commands
+    let (cmd, matches) = match (cmd, matches.subcommand()) {
+        ("bundle", ("export", Some(matches))) => ("export", matches),
+        ("access", ("add", Some(matches))) => ("access-add", matches),
+        ("access", ("remove", Some(matches))) => ("access-remove", matches),
+        _ => (cmd, matches),
+    };
 
     // process all files
     let mut files = Vec::<(String, String)>::new();
-    let prefix = matches.value_of("prefix").unwrap();
+    let prefix = matches.value_of("prefix").unwrap_or("");
     let mut outiter = matches
         .values_of("output")
         .unwrap_or(clap::Values::default());
+    let mut inputs = Vec::new();
     for input in matches.values_of("input").unwrap() {
+        // gc looks for documents in whole directory trees
+        if cmd == "gc" {
+            match gc::expand(input) {
+                Ok(found) => inputs.extend(found),
+                Err(e) => {
+                    eprintln!("Failed to read {}: {}", input, e);
+                    ::std::process::exit(1);
+                }
+            }
+        } else {
+            inputs.push(input.to_string());
+        }
+    }
+    for input in inputs {
         if let Some(output) = outiter.next() {
             files.push((input.to_string(), output.to_string()));
         } else {
@@ -501,27 +1281,97 @@ where
             if input == "-" {
                 output = "-".to_string();
             }
-            files.push((input.to_string(), output));
+            files.push((input.clone(), output));
         }
     }
 
+    // explicit separators apply to every file
+    let cli_separators = Separators {
+        syntax: matches.value_of("syntax").map(|s| s.to_string()),
+        left_sep: explicit(matches, "left-separator").map(|s| s.to_string()),
+        right_sep: explicit(matches, "right-separator").map(|s| s.to_string()),
+    };
+
+    let json = matches.occurrences_of("json") != 0;
+    let mut listings = Vec::new();
+    let mut changed = false;
+    let mut failed = false;
+    let mut reachable = gc::Reachable::default();
+    let mut gc_cas: Option<String> = None;
+    let mut exported = bundle::Bundle {
+        documents: Vec::new(),
+        objects: BTreeMap::new(),
+    };
+    let backup = if matches.occurrences_of("backup") != 0 {
+        Some(
+            matches
+                .value_of("backup")
+                .unwrap_or(consts::DEFAULT_BACKUP_SUFFIX),
+        )
+    } else {
+        None
+    };
+
+    let mut configs = HashMap::new();
+    let trusted = matches.occurrences_of("trust-config") != 0;
+    let mut current: Option<(Option<PathBuf>, ParseOps)> = None;
     for (path_in, path_out) in files {
+        let config = if matches.occurrences_of("no-config") != 0 {
+            None
+        } else {
+            match find_config(&mut configs, &path_in, trusted) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("{}, aborting.", e);
+                    ::std::process::exit(1);
+                }
+            }
+        };
+
+        // the notes of the previous input
+        if let Some((_, ref mut paops)) = current {
+            print_notes(paops);
+        }
+
+        // settings are rebuilt whenever the configuration changes
+        let config_path = config.as_ref().map(|c| c.path.clone());
+        let rebuild = match current {
+            Some((ref path, _)) => *path != config_path,
+            None => true,
+        };
+        if rebuild {
+            let paops = make_paops(&mut app, cmd, matches, config.as_ref().map(|c| c.as_ref()));
+            if paops.verbose {
+                match config_path {
+                    Some(ref path) => eprintln!("Using configuration {}", path.display()),
+                    None => eprintln!("No configuration file found"),
+                }
+            }
+            current = Some((config_path, paops));
+        }
+        let paops = &mut current.as_mut().unwrap().1;
+
         if paops.verbose {
             eprintln!("Reading {}", path_in);
         }
 
-        // open input file
-        let reader_in: Box<dyn BufRead> = if path_in == "-" {
-            Box::new(BufReader::new(std::io::stdin()))
+        // read input file, keeping the original around for --dry-run
+        let mut original = Vec::new();
+        let read = if path_in == "-" {
+            std::io::stdin().read_to_end(&mut original)
         } else {
             match File::open(&path_in) {
-                Ok(file_in) => Box::new(BufReader::new(file_in)),
+                Ok(mut file_in) => file_in.read_to_end(&mut original),
                 Err(e) => {
                     eprintln!("Failed to open {} for reading: {}", path_in, e);
                     ::std::process::exit(1);
                 }
             }
         };
+        if let Err(e) = read {
+            eprintln!("Failed to read {}: {}", path_in, e);
+            ::std::process::exit(1);
+        }
 
         // parse input
         paops.fname = if path_in == "-" {
@@ -529,44 +1379,252 @@ where
         } else {
             path_in.to_string()
         };
-        let tree_in = match etree::parse(reader_in, &mut paops) {
-            Ok(tree) => tree,
+
+        // separators: defaults, configuration, per-glob overrides, command line
+        paops.left_sep = consts::DEFAULT_LEFT_SEP.to_string();
+        paops.right_sep = consts::DEFAULT_RIGHT_SEP.to_string();
+        if let Some(ref config) = config {
+            config.separators.apply(paops, &path_in);
+            if let Some(separators) = fs::canonicalize(&path_in)
+                .ok()
+                .and_then(|path| config.separators_for(&path))
+            {
+                separators.apply(paops, &path_in);
+            }
+        }
+        cli_separators.apply(paops, &path_in);
+
+        // print some of the processing parameters if verbose
+        if paops.verbose {
+            let cas = match paops.cas {
+                Some(ref backend) => backend.url(),
+                None => paops.casdir.display().to_string(),
+            };
+            eprintln!(
+                "LEFT_SEP='{}' RIGHT_SEP='{}' casdir = '{}'",
+                paops.left_sep, paops.right_sep, cas,
+            );
+        }
+        let parsed = Document::parse(&original[..], paops);
+        print_notes(paops);
+        let doc_in = match parsed {
+            Ok(doc) => doc,
+            Err(e) if cmd == "verify" => {
+                println!("{}: FAILED: {}", paops.fname, e);
+                failed = true;
+                continue;
+            }
+            Err(e) if cmd == "gc" => {
+                reachable.unexamined.push(format!("{}: {}", paops.fname, e));
+                continue;
+            }
             Err(e) => {
-                eprintln!("{} in {}, aborting.", e, path_in);
+                eprintln!("{}, aborting.", e);
                 ::std::process::exit(1);
             }
         };
 
+        // list it
+        if cmd == "ls" {
+            let segments = doc_in.segments(paops);
+            if json {
+                listings.push((paops.fname.clone(), segments));
+            } else {
+                print!("{}", ls::text(&paops.fname, &segments));
+            }
+            continue;
+        }
+
+        // check it
+        if cmd == "verify" {
+            let checks = doc_in.verify(paops);
+            print!("{}", verify::text(&paops.fname, &checks));
+            if !checks.iter().all(Check::passed) {
+                failed = true;
+            }
+            continue;
+        }
+
+        // collect its references, all of which must be in the same CAS
+        if cmd == "gc" {
+            let url = cas::url(paops);
+            if gc_cas.get_or_insert_with(|| url.clone()) != &url {
+                eprintln!(
+                    "{} uses {} rather than {}, aborting.",
+                    paops.fname,
+                    url,
+                    gc_cas.unwrap()
+                );
+                ::std::process::exit(1);
+            }
+            let checks = doc_in.verify(paops);
+            reachable.add(&paops.fname, &checks);
+            continue;
+        }
+
+        // collect the objects it refers to, from its own CAS
+        if cmd == "export" {
+            let checks = doc_in.verify(paops);
+            reachable.add(&paops.fname, &checks);
+            for id in &reachable.ids {
+                if exported.objects.contains_key(id) {
+                    continue;
+                }
+                match cas::load(id, paops) {
+                    Ok(blob) => exported.objects.insert(id.clone(), blob),
+                    Err(e) => {
+                        print_notes(paops);
+                        eprintln!("{} in {}, aborting.", e, path_in);
+                        ::std::process::exit(1);
+                    }
+                };
+            }
+            exported.documents.push(paops.fname.clone());
+            continue;
+        }
+
         // transform it
         if paops.verbose {
             eprintln!("Transforming {}", path_in);
         }
-        let tree_out = match etree::transform(&tree_in, &mut paops) {
-            Ok(tree) => tree,
+        let transformed = doc_in.transform(paops);
+        print_notes(paops);
+        let doc_out = match transformed {
+            Ok(doc) => doc,
             Err(e) => {
                 eprintln!("{} in {}, aborting.", e, path_in);
                 ::std::process::exit(1);
             }
         };
 
-        // write it out
-        if paops.verbose {
-            eprintln!("Writing {}", path_out);
+        // render the output in memory first
+        let mut modified = Vec::new();
+        if let Err(e) = doc_out.write(&mut modified, paops) {
+            eprintln!("Failed to write {}: {}", path_out, e);
+            ::std::process::exit(1);
         }
 
-        // open output file
-        let mut writer_out: Box<dyn Write> = if path_out == "-" {
-            Box::new(BufWriter::new(std::io::stdout()))
-        } else {
-            match File::create(&path_out) {
-                Ok(file_out) => Box::new(BufWriter::new(file_out)),
-                Err(e) => {
-                    eprintln!("Failed to open {} for writing: {}", path_out, e);
-                    ::std::process::exit(1);
+        // only show what would change
+        if paops.dry_run {
+            if original != modified {
+                changed = true;
+                let diff = unidiff::unified(
+                    &String::from_utf8_lossy(&original),
+                    &String::from_utf8_lossy(&modified),
+                    &paops.fname,
+                    &path_out,
+                );
+                if diff.is_empty() {
+                    // the changes are all in bytes that aren't valid UTF-8
+                    println!("Files {} and {} differ", paops.fname, path_out);
+                } else {
+                    print!("{}", diff);
                 }
             }
+            continue;
+        }
+
+        if path_out == "-" {
+            let stdout = std::io::stdout();
+            let mut writer_out = stdout.lock();
+            if let Err(e) = writer_out
+                .write_all(&modified)
+                .and_then(|_| writer_out.flush())
+            {
+                eprintln!("Failed to write {}: {}", path_out, e);
+                ::std::process::exit(1);
+            }
+            continue;
+        }
+
+        // leave files alone if they wouldn't change
+        let unchanged = if path_out == path_in {
+            modified == original
+        } else {
+            fs::read(&path_out)
+                .map(|old| old == modified)
+                .unwrap_or(false)
         };
+        if unchanged {
+            if paops.verbose {
+                eprintln!("{} is unchanged, not writing", path_out);
+            }
+            continue;
+        }
+
+        // write it out, replacing the old file only once complete
+        if paops.verbose {
+            eprintln!("Writing {}", path_out);
+        }
+        if let Err(e) = atomic::write(Path::new(&path_out), &modified, backup) {
+            eprintln!("Failed to write {}: {}", path_out, e);
+            ::std::process::exit(1);
+        }
+    }
 
-        etree::tree_write(&mut writer_out, &tree_out, &mut paops);
+    if let Some((_, ref mut paops)) = current {
+        print_notes(paops);
+    }
+    if json {
+        print!("{}", ls::json(&listings));
+    }
+    if cmd == "export" {
+        if let Some(report) = reachable.unexamined_report(
+            "Warning: the bundle may be incomplete, these contents couldn't be examined:",
+        ) {
+            eprintln!("{}", report);
+        }
+        let path = matches.value_of("bundle").unwrap();
+        let mut data = Vec::new();
+        let written = exported.write(&mut data).and_then(|_| {
+            if path == "-" {
+                let stdout = std::io::stdout();
+                let mut writer_out = stdout.lock();
+                writer_out.write_all(&data).and_then(|_| writer_out.flush())
+            } else {
+                atomic::write(Path::new(path), &data, None)
+            }
+        });
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", path, e);
+            ::std::process::exit(1);
+        }
+    }
+    if cmd == "gc" {
+        match current {
+            Some((_, ref mut paops)) => {
+                let collected =
+                    gc::collect(&reachable, paops, matches.occurrences_of("delete") != 0);
+                print_notes(paops);
+                match collected {
+                    Ok(collected) => {
+                        if let Some(ref warning) = collected.warning {
+                            eprintln!("{}", warning);
+                        }
+                        for line in &collected.lines {
+                            println!("{}", line);
+                        }
+                        for e in &collected.errors {
+                            eprintln!("{}", e);
+                        }
+                        if !collected.ok() {
+                            failed = true;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{}, aborting.", e);
+                        failed = true;
+                    }
+                }
+            }
+            None => eprintln!("No documents found, not collecting anything."),
+        }
+    }
+    if failed {
+        ::std::process::exit(1);
+    }
+    // let scripts tell "would change" apart from errors
+    if changed {
+        ::std::process::exit(consts::EXIT_DRY_RUN_CHANGED);
     }
 }
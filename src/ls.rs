@@ -0,0 +1,131 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::fmt::Write;
+
+use document::Segment;
+
+// plain text listing of a document, one line per segment
+
+pub fn text(path: &str, segments: &[Segment]) -> String {
+    let mut out = format!("{}:\n", path);
+    text_segments(&mut out, segments, 1);
+    out
+}
+
+fn text_segments(out: &mut String, segments: &[Segment], depth: usize) {
+    for seg in segments {
+        write!(
+            out,
+            "{}{} {} lines {}-{}",
+            "  ".repeat(depth),
+            seg.keyword,
+            seg.state.name(),
+            seg.lines.0,
+            seg.lines.1
+        )
+        .unwrap();
+        if let Some(ref cas) = seg.cas {
+            write!(out, " cas {}", cas).unwrap();
+        }
+        if let Some(size) = seg.size {
+            write!(out, " size {}", size).unwrap();
+        }
+        if let Some((ref alg, ref params)) = seg.pbkdf {
+            write!(out, " pbkdf {}", alg).unwrap();
+            if !params.is_empty() {
+                write!(out, " ({})", params).unwrap();
+            }
+        }
+        if let Some(ref cipher) = seg.cipher {
+            write!(out, " cipher {}", cipher).unwrap();
+        }
+        out.push('\n');
+        text_segments(out, &seg.children, depth + 1);
+    }
+}
+
+// JSON listing of several documents
+
+pub fn json(files: &[(String, Vec<Segment>)]) -> String {
+    let files: Vec<String> = files
+        .iter()
+        .map(|(path, segments)| {
+            format!(
+                "{{\"file\":{},\"segments\":{}}}",
+                json_string(path),
+                json_segments(segments)
+            )
+        })
+        .collect();
+    format!("[{}]\n", files.join(","))
+}
+
+fn json_segments(segments: &[Segment]) -> String {
+    let segments: Vec<String> = segments
+        .iter()
+        .map(|seg| {
+            let mut fields = vec![
+                format!("\"keyword\":{}", json_string(&seg.keyword)),
+                format!("\"state\":{}", json_string(seg.state.name())),
+                format!("\"lines\":[{},{}]", seg.lines.0, seg.lines.1),
+            ];
+            if let Some(ref cas) = seg.cas {
+                fields.push(format!("\"cas\":{}", json_string(cas)));
+            }
+            if let Some(size) = seg.size {
+                fields.push(format!("\"size\":{}", size));
+            }
+            if let Some((ref alg, ref params)) = seg.pbkdf {
+                fields.push(format!(
+                    "\"pbkdf\":{{\"alg\":{},\"params\":{}}}",
+                    json_string(alg),
+                    json_string(params)
+                ));
+            }
+            if let Some(ref cipher) = seg.cipher {
+                fields.push(format!("\"cipher\":{}", json_string(cipher)));
+            }
+            fields.push(format!("\"segments\":{}", json_segments(&seg.children)));
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    format!("[{}]", segments.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
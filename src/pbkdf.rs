@@ -26,6 +26,7 @@ use std::collections::BTreeMap;
 
 use crypto;
 use crypto::CryptoPolicy;
+use error::EnprotError;
 use etree;
 use utils;
 
@@ -51,7 +52,7 @@ fn pbkdf_legacy(
     password: &str,
     key_len: usize,
     policy: &Box<dyn CryptoPolicy>,
-) -> Result<Vec<u8>, &'static str> {
+) -> Result<Vec<u8>, EnprotError> {
     policy.check_pbkdf("sha3-512", key_len, password, &[], &BTreeMap::new())?;
     let mut result = crypto::digest("sha3-512", password.as_bytes(), policy)?;
     result.truncate(key_len);
@@ -66,7 +67,7 @@ fn pbkdf_timed(
     msec: u32,
     key_len: usize,
     policy: &Box<dyn CryptoPolicy>,
-) -> Result<(Vec<u8>, BTreeMap<String, usize>), &'static str> {
+) -> Result<(Vec<u8>, BTreeMap<String, usize>), EnprotError> {
     crypto::derive_key_from_password_timed(
         alg,
         botan_param_order,
@@ -86,7 +87,7 @@ fn pbkdf_manual(
     params_map: BTreeMap<String, usize>,
     key_len: usize,
     policy: &Box<dyn CryptoPolicy>,
-) -> Result<Vec<u8>, &'static str> {
+) -> Result<Vec<u8>, EnprotError> {
     crypto::derive_key_from_password(
         alg,
         botan_param_order,
@@ -118,20 +119,24 @@ pub fn derive_key(
     opts: &etree::PBKDFOptions,
     cache: &mut Option<PBKDFCache>,
     policy: &Box<dyn CryptoPolicy>,
-) -> Result<(Vec<u8>, Option<String>), &'static str> {
+) -> Result<(Vec<u8>, Option<String>), EnprotError> {
     if opts.alg == "legacy" {
         return Ok((pbkdf_legacy(password, key_len, policy)?, None));
     }
-    let mut salt = opts.salt.clone().unwrap_or_else(|| {
-        rng.as_ref()
-            .unwrap()
+    let mut salt = match opts.salt.clone() {
+        Some(salt) => salt,
+        None => rng
+            .as_ref()
+            .ok_or(EnprotError::Crypto("Missing RNG"))?
             .read(opts.saltlen)
-            .map_err(|_| "Failed to read from RNG")
-            .unwrap()
-    });
-    let botan_param_order = BOTAN_PBKDF_PARAM_MAP
-        .get::<str>(&opts.alg)
-        .ok_or("Missing PBKDF param mapping")?;
+            .map_err(|_| EnprotError::Crypto("Failed to read from RNG"))?,
+    };
+    let botan_param_order = BOTAN_PBKDF_PARAM_MAP.get::<str>(&opts.alg).ok_or_else(|| {
+        EnprotError::InvalidAlgorithm {
+            kind: "PBKDF",
+            alg: opts.alg.clone(),
+        }
+    })?;
     if let Some(params) = opts.params.as_ref() {
         let key;
         if let Some(entry) = cache.as_ref().unwrap_or(&Vec::new()).iter().find(|e| {
@@ -140,6 +145,7 @@ pub fn derive_key(
                 && e.key.len() == key_len
                 && e.msec == 0
                 && e.params == *params
+                && e.salt == salt
         }) {
             key = entry.key.clone();
         } else {
@@ -168,12 +174,10 @@ pub fn derive_key(
             Some(format_phc(&opts.alg, opts.params.as_ref().unwrap(), &salt)),
         ));
     }
+    let msec = opts.msec.ok_or(EnprotError::Crypto("Missing PBKDF msec"))?;
     let (key, params);
     if let Some(entry) = cache.as_ref().unwrap_or(&Vec::new()).iter().find(|e| {
-        e.password == password
-            && e.alg == opts.alg
-            && e.key.len() == key_len
-            && e.msec == opts.msec.unwrap()
+        e.password == password && e.alg == opts.alg && e.key.len() == key_len && e.msec == msec
     }) {
         salt = entry.salt.clone();
         key = entry.key.clone();
@@ -184,7 +188,7 @@ pub fn derive_key(
             &botan_param_order,
             password,
             &salt,
-            opts.msec.ok_or("Missing PBKDF msec")?,
+            msec,
             key_len,
             policy,
         )?;
@@ -194,7 +198,7 @@ pub fn derive_key(
             cache.as_mut().unwrap().push(PBKDFCacheEntry {
                 password: password.to_string(),
                 alg: opts.alg.clone(),
-                msec: opts.msec.unwrap(),
+                msec,
                 salt: salt.clone(),
                 key: key.clone(),
                 params: params.clone(),
@@ -23,6 +23,8 @@
 
 use std::collections::BTreeMap;
 
+use error::EnprotError;
+
 use policy::CryptoPolicy;
 
 pub struct CryptoPolicyDefault {}
@@ -36,7 +38,7 @@ impl CryptoPolicyDefault {
 
 // allow everything
 impl CryptoPolicy for CryptoPolicyDefault {
-    fn check_hash(&self, _alg: &str) -> Result<(), &'static str> {
+    fn check_hash(&self, _alg: &str) -> Result<(), EnprotError> {
         Ok(())
     }
 
@@ -47,7 +49,7 @@ impl CryptoPolicy for CryptoPolicyDefault {
         _password: &str,
         _salt: &[u8],
         _params: &BTreeMap<String, usize>,
-    ) -> Result<(), &'static str> {
+    ) -> Result<(), EnprotError> {
         Ok(())
     }
 
@@ -57,7 +59,11 @@ impl CryptoPolicy for CryptoPolicyDefault {
         _key: &[u8],
         _iv: &[u8],
         _ad: &[u8],
-    ) -> Result<(), &'static str> {
+    ) -> Result<(), EnprotError> {
+        Ok(())
+    }
+
+    fn check_key_agreement(&self, _alg: &str) -> Result<(), EnprotError> {
         Ok(())
     }
 
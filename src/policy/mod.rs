@@ -23,11 +23,14 @@
 
 use std::collections::BTreeMap;
 
+use consts;
+use error::EnprotError;
+
 pub mod default;
 pub mod nist;
 
 pub trait CryptoPolicy {
-    fn check_hash(&self, alg: &str) -> Result<(), &'static str>;
+    fn check_hash(&self, alg: &str) -> Result<(), EnprotError>;
 
     fn check_pbkdf(
         &self,
@@ -36,13 +39,17 @@ pub trait CryptoPolicy {
         password: &str,
         salt: &[u8],
         params: &BTreeMap<String, usize>,
-    ) -> Result<(), &'static str>;
+    ) -> Result<(), EnprotError>;
+
+    fn check_cipher(&self, alg: &str, key: &[u8], iv: &[u8], ad: &[u8]) -> Result<(), EnprotError>;
 
-    fn check_cipher(&self, alg: &str, key: &[u8], iv: &[u8], ad: &[u8])
-        -> Result<(), &'static str>;
+    fn check_key_agreement(&self, alg: &str) -> Result<(), EnprotError>;
 
     fn default_pbkdf_alg(&self) -> String;
     fn default_pbkdf_salt_length(&self) -> usize;
     fn default_pbkdf_millis(&self) -> u32;
     fn default_cipher_alg(&self) -> String;
+    fn default_cas_hash_alg(&self) -> String {
+        consts::DEFAULT_CAS_HASH_ALG.to_string()
+    }
 }
@@ -24,6 +24,7 @@
 use phf::phf_set;
 use std::collections::BTreeMap;
 
+use error::EnprotError;
 use policy::default::CryptoPolicyDefault;
 use policy::CryptoPolicy;
 
@@ -48,24 +49,25 @@ impl CryptoPolicyNIST {
     };
     const NIST_PBKDF_MIN_SALT_LEN: usize = 16;
 
-    fn check_alg(&self, kind: &str, alg: &str) -> Result<(), &'static str> {
+    fn check_alg(&self, kind: &str, alg: &str) -> Result<(), EnprotError> {
         let lst = match kind {
             "Cipher" => &Self::NIST_APPROVED_CIPHERS,
             "Hash" => &Self::NIST_APPROVED_HASHES,
             "PBKDF" => &Self::NIST_APPROVED_PBKDFS,
-            _ => return Err("Invalid algorithm kind"),
+            _ => return Err(EnprotError::policy("Invalid algorithm kind")),
         };
         if lst.contains(alg) {
             Ok(())
         } else {
-            eprintln!("{} algorithm is not permitted by policy: {}", kind, alg);
-            Err("Algorithm not permitted by policy")
+            Err(EnprotError::Policy {
+                rule: format!("{} algorithm is not permitted by policy: {}", kind, alg),
+            })
         }
     }
 }
 
 impl CryptoPolicy for CryptoPolicyNIST {
-    fn check_hash(&self, alg: &str) -> Result<(), &'static str> {
+    fn check_hash(&self, alg: &str) -> Result<(), EnprotError> {
         self.check_alg("Hash", alg)
     }
 
@@ -76,17 +78,17 @@ impl CryptoPolicy for CryptoPolicyNIST {
         _password: &str,
         salt: &[u8],
         params: &BTreeMap<String, usize>,
-    ) -> Result<(), &'static str> {
+    ) -> Result<(), EnprotError> {
         self.check_alg("PBKDF", alg)?;
         if salt.len() < Self::NIST_PBKDF_MIN_SALT_LEN {
-            return Err("Salt length violates policy");
+            return Err(EnprotError::policy("Salt length violates policy"));
         }
         if key_len < 14 {
-            return Err("Key length violates policy");
+            return Err(EnprotError::policy("Key length violates policy"));
         }
         if let Some(iters) = params.get("i") {
             if *iters < 1000 {
-                return Err("Iteration count violates policy");
+                return Err(EnprotError::policy("Iteration count violates policy"));
             }
         }
         Ok(())
@@ -98,14 +100,26 @@ impl CryptoPolicy for CryptoPolicyNIST {
         _key: &[u8],
         iv: &[u8],
         _ad: &[u8],
-    ) -> Result<(), &'static str> {
+    ) -> Result<(), EnprotError> {
         self.check_alg("Cipher", alg)?;
         if alg == "aes-256-gcm" && iv.len() != 96 / 8 {
-            return Err("IV length does not match NIST recommendations for this cipher.");
+            return Err(EnprotError::policy(
+                "IV length does not match NIST recommendations for this cipher.",
+            ));
         }
         Ok(())
     }
 
+    fn check_key_agreement(&self, alg: &str) -> Result<(), EnprotError> {
+        // no approved scheme is available yet
+        Err(EnprotError::Policy {
+            rule: format!(
+                "Key agreement algorithm is not permitted by policy: {}",
+                alg
+            ),
+        })
+    }
+
     fn default_pbkdf_alg(&self) -> String {
         Self::DEFAULT_PBKDF_ALG.to_string()
     }
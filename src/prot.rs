@@ -24,45 +24,107 @@
 use std::collections::BTreeMap;
 
 use cipher;
+use consts;
 use crypto::CryptoPolicy;
+use error::EnprotError;
 use etree;
 use pbkdf::derive_key;
 use pbkdf::PBKDFCache;
+use recipient;
 use utils;
 
 // Get a password
 
-pub fn get_password(name: &str, rep: bool) -> String {
-    let prompt = "Password for ".to_string() + name + ": ";
-    let mut pass = rpassword::prompt_password_stdout(&prompt).unwrap();
-    if rep {
-        let prompt = "Repeat password for ".to_string() + name + ": ";
-        let pass2 = rpassword::prompt_password_stdout(&prompt).unwrap();
-        if pass != pass2 {
-            eprintln!("Password mismatch. Try again.");
-            pass = get_password(name, rep);
+pub fn get_password(name: &str, rep: bool) -> Result<String, EnprotError> {
+    let mut prompt = "Password for ".to_string() + name + ": ";
+    loop {
+        let pass = rpassword::prompt_password_stdout(&prompt).map_err(EnprotError::Prompt)?;
+        if !rep {
+            return Ok(pass);
         }
+        let repeat = "Repeat password for ".to_string() + name + ": ";
+        let pass2 = rpassword::prompt_password_stdout(&repeat).map_err(EnprotError::Prompt)?;
+        if pass == pass2 {
+            return Ok(pass);
+        }
+        // the prompt tells of the mismatch
+        prompt = "Password mismatch. Try again.\nPassword for ".to_string() + name + ": ";
     }
-    pass
 }
 
-// Encrypt
+// Associated data, binding a ciphertext to its keyword, extfields and
+// document id. Segments without a version extfield predate this and are
+// authenticated without any. Password stanzas are left out, so that access
+// can change without re-encrypting; each of them authenticates itself.
 
+fn associated_data(
+    keyw: &str,
+    extfields: &BTreeMap<String, String>,
+    document_id: &Option<String>,
+) -> Result<Vec<u8>, EnprotError> {
+    match extfields.get("version").map(|v| v.as_str()) {
+        None => Ok(Vec::new()),
+        Some(consts::FORMAT_VERSION) => {
+            let mut ad = format!("enprot\0{}\0", keyw);
+            for (key, value) in extfields.iter() {
+                if key.starts_with("password.") {
+                    continue;
+                }
+                ad += &format!("{}:{}\0", key, value);
+            }
+            if let Some(id) = document_id {
+                ad += id;
+            }
+            Ok(ad.into_bytes())
+        }
+        Some(_) => Err(EnprotError::Crypto("Unsupported format version")),
+    }
+}
+
+// Encrypt under a random data key, unless one is given for testing, wrapped
+// in a password stanza for the password if any and for each of the recipients
+
+#[allow(clippy::too_many_arguments)]
 pub fn encrypt(
+    keyw: &str,
     pt: Vec<u8>,
-    password: &str,
+    password: Option<&str>,
+    recipients: &[Vec<u8>],
     rng: &Option<botan::RandomNumberGenerator>,
     pbkdfopts: &etree::PBKDFOptions,
     cipheropts: &etree::CipherOptions,
+    document_id: &Option<String>,
     cache: &mut Option<PBKDFCache>,
     policy: &Box<dyn CryptoPolicy>,
-) -> Result<(Vec<u8>, BTreeMap<String, String>), &'static str> {
+) -> Result<(Vec<u8>, BTreeMap<String, String>), EnprotError> {
     let enc = cipher::encryption(&cipheropts.alg)?;
     let key_len = enc.key_len_max();
-    let (key, pbkdf) = derive_key(password, key_len, rng, pbkdfopts, cache, policy)?;
+    if password.is_none() && recipients.is_empty() {
+        return Err(EnprotError::Crypto("Missing password"));
+    }
+    let key = if let Some(mykey) = cipheropts.key.clone() {
+        if mykey.len() != key_len {
+            return Err(EnprotError::Crypto(
+                "Data key length does not match the cipher",
+            ));
+        }
+        mykey
+    } else {
+        rng.as_ref()
+            .ok_or(EnprotError::Crypto("Missing RNG"))?
+            .read(key_len)
+            .map_err(|_| EnprotError::Crypto("RNG error"))?
+    };
     let mut extfields: BTreeMap<String, String> = BTreeMap::new();
-    if pbkdf != None {
-        extfields.insert("pbkdf".to_string(), pbkdf.unwrap());
+    extfields.insert("version".to_string(), consts::FORMAT_VERSION.to_string());
+    if let Some(password) = password {
+        extfields = add_password(&key, password, &extfields, rng, pbkdfopts, cache, policy)?;
+    }
+    for (i, public) in recipients.iter().enumerate() {
+        extfields.insert(
+            format!("recipient.{}", i),
+            recipient::wrap(&key, public, rng.as_ref().unwrap(), policy)?,
+        );
     }
     let mut iv: Vec<u8> = Vec::new();
     if cipheropts.alg != "aes-256-siv" {
@@ -72,9 +134,9 @@ pub fn encrypt(
         } else {
             let ivlen = enc.nonce_len();
             rng.as_ref()
-                .ok_or("Missing RNG")?
+                .ok_or(EnprotError::Crypto("Missing RNG"))?
                 .read(ivlen)
-                .map_err(|_| "RNG error")?
+                .map_err(|_| EnprotError::Crypto("RNG error"))?
         };
         extfields.insert(
             "cipher".to_string(),
@@ -82,30 +144,67 @@ pub fn encrypt(
         );
     } else if cipheropts.iv != None {
         // IV not required
-        return Err("IV was supplied but not expected");
+        return Err(EnprotError::Crypto("IV was supplied but not expected"));
     }
-    Ok((enc.process(&key, &iv, &[], &pt, policy)?, extfields))
+    let ad = associated_data(keyw, &extfields, document_id)?;
+    Ok((enc.process(&key, &iv, &ad, &pt, policy)?, extfields))
 }
 
 // Decrypt
 
-pub fn decrypt(
-    ct: Vec<u8>,
-    password: &str,
-    pbkdf: &Option<&String>,
-    cipher: &Option<&String>,
+fn recipient_stanzas(extfields: &BTreeMap<String, String>) -> Vec<&String> {
+    extfields
+        .iter()
+        .filter(|(key, _)| key.starts_with("recipient."))
+        .map(|(_, stanza)| stanza)
+        .collect()
+}
+
+pub fn password_stanzas(extfields: &BTreeMap<String, String>) -> Vec<(&String, &String)> {
+    extfields
+        .iter()
+        .filter(|(key, _)| key.starts_with("password."))
+        .collect()
+}
+
+// whether the segment is encrypted under a data key wrapped in stanzas,
+// rather than under a key derived from its password
+pub fn has_data_key(extfields: &BTreeMap<String, String>) -> bool {
+    extfields.contains_key("version") && !extfields.contains_key("pbkdf")
+}
+
+// whether a password may decrypt the segment, rather than only identities
+pub fn password_protected(extfields: &BTreeMap<String, String>) -> bool {
+    extfields.contains_key("pbkdf")
+        || !password_stanzas(extfields).is_empty()
+        || recipient_stanzas(extfields).is_empty()
+}
+
+// the plaintext and the data key of a segment
+pub type Opened = (Vec<u8>, Vec<u8>);
+
+// decrypt with the key of the segment, parsing the cipher extfield
+fn open(
+    keyw: &str,
+    ct: &[u8],
+    key: KeySource,
+    extfields: &BTreeMap<String, String>,
+    document_id: &Option<String>,
     cache: &mut Option<PBKDFCache>,
     policy: &Box<dyn CryptoPolicy>,
-) -> Result<Vec<u8>, &'static str> {
+) -> Result<Option<Opened>, EnprotError> {
+    let ad = associated_data(keyw, extfields, document_id)?;
     let cipher_alg;
     let mut iv = Vec::new();
-    if let Some(cipher) = cipher {
+    if let Some(cipher) = extfields.get("cipher") {
         let mut it = cipher.split("$");
-        cipher_alg = it.next().ok_or("Invalid cipher extfield")?;
+        cipher_alg = it
+            .next()
+            .ok_or(EnprotError::Crypto("Invalid cipher extfield"))?;
         let mut fields = BTreeMap::new();
         for val in it {
             let mut it = val.splitn(2, '=');
-            let key = it.next().ok_or("Missing field key")?;
+            let key = it.next().ok_or(EnprotError::Crypto("Missing field key"))?;
             let value = it.collect::<String>();
             fields.insert(key, value);
         }
@@ -116,46 +215,280 @@ pub fn decrypt(
         cipher_alg = "aes-256-siv";
     }
     let dec = cipher::decryption(&cipher_alg)?;
-    let key_len = dec.key_len_max();
-    let key: Vec<u8>;
-    if let Some(pbkdf) = pbkdf {
-        let phc: phc::raw::RawPHC = pbkdf.parse().map_err(|_| "Failed to parse PHC")?;
-        let alg = phc.id();
-        let mut params_map: BTreeMap<String, usize> = BTreeMap::new();
-        params_map.extend(
-            phc.params()
-                .iter()
-                .map(|v| (v.0.to_string(), v.1.parse::<usize>().unwrap())),
-        );
-        let salt = match phc.salt().ok_or("Missing salt")? {
-            phc::Salt::Ascii(s) => utils::base64_decode(s)?,
-            phc::Salt::Binary(b) => utils::base64_decode(std::str::from_utf8(b).unwrap())?,
-        };
-        let pbkdfopts = etree::PBKDFOptions {
-            alg: alg.to_string(),
-            saltlen: 0,
-            salt: Some(salt),
-            msec: None,
-            params: Some(params_map),
-        };
-        let (thekey, _) = derive_key(password, key_len, &None, &pbkdfopts, cache, policy)?;
-        key = thekey;
+    let key = match key {
+        KeySource::Password(password) => {
+            password_key(password, dec.key_len_max(), extfields, cache, policy)?
+        }
+        KeySource::Identities(identities) => match identity_key(identities, extfields, policy)? {
+            Some(key) => key,
+            None => return Ok(None),
+        },
+    };
+    Ok(Some((dec.process(&key, &iv, &ad, ct, policy)?, key)))
+}
+
+enum KeySource<'a> {
+    Password(&'a str),
+    Identities(&'a [Vec<u8>]),
+}
+
+// the legacy KDF, which has neither parameters nor a salt
+fn legacy_pbkdf() -> etree::PBKDFOptions {
+    etree::PBKDFOptions {
+        alg: "legacy".to_string(),
+        saltlen: 0,
+        salt: None,
+        msec: None,
+        params: None,
+    }
+}
+
+// the options of a PHC string "$alg$params$salt", and the data key wrapped
+// under the key they derive if "$wrapped" follows; a key wrapped under the
+// legacy KDF is kept as "$legacy$$wrapped"
+fn parse_pbkdf(pbkdf: &str) -> Result<(etree::PBKDFOptions, Option<Vec<u8>>), EnprotError> {
+    if let Some(wrapped) = pbkdf.strip_prefix(consts::LEGACY_STANZA) {
+        return Ok((legacy_pbkdf(), Some(utils::base64_decode(wrapped)?)));
+    }
+    let (pbkdf, wrapped) = if pbkdf.matches('$').count() == 4 {
+        let mut it = pbkdf.rsplitn(2, '$');
+        let wrapped = it.next().map(utils::base64_decode).transpose()?;
+        (it.next().unwrap_or(""), wrapped)
     } else {
-        let (thekey, _) = derive_key(
-            password,
-            key_len,
-            &None,
-            &etree::PBKDFOptions {
-                alg: "legacy".to_string(),
-                saltlen: 0,
-                salt: None,
-                msec: None,
-                params: None,
-            },
-            cache,
-            policy,
-        )?;
-        key = thekey;
-    }
-    Ok(dec.process(&key, &iv, &[], &ct, policy)?)
+        (pbkdf, None)
+    };
+    let phc: phc::raw::RawPHC = pbkdf
+        .parse()
+        .map_err(|_| EnprotError::Crypto("Failed to parse PHC"))?;
+    let alg = phc.id();
+    let mut params_map: BTreeMap<String, usize> = BTreeMap::new();
+    for (key, value) in phc.params().iter() {
+        let value = value
+            .parse::<usize>()
+            .map_err(|_| EnprotError::Crypto("Invalid PHC parameter"))?;
+        params_map.insert(key.to_string(), value);
+    }
+    let salt = match phc.salt().ok_or(EnprotError::Crypto("Missing salt"))? {
+        phc::Salt::Ascii(s) => utils::base64_decode(s)?,
+        phc::Salt::Binary(b) => utils::base64_decode(
+            std::str::from_utf8(b).map_err(|_| EnprotError::Crypto("Invalid PHC salt"))?,
+        )?,
+    };
+    let pbkdfopts = etree::PBKDFOptions {
+        alg: alg.to_string(),
+        saltlen: 0,
+        salt: Some(salt),
+        msec: None,
+        params: Some(params_map),
+    };
+    Ok((pbkdfopts, wrapped))
+}
+
+// the data key wrapped in a PHC string, if the password unwraps it
+fn unwrap_password(
+    password: &str,
+    pbkdfopts: &etree::PBKDFOptions,
+    wrapped: &[u8],
+    cache: &mut Option<PBKDFCache>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<Option<Vec<u8>>, EnprotError> {
+    let (kek, _) = derive_key(password, consts::KEK_LEN, &None, pbkdfopts, cache, policy)?;
+    Ok(cipher::unwrap_key(&kek, wrapped, policy))
+}
+
+// the data key of the password stanza the password opens, if any
+fn password_stanza_key(
+    password: &str,
+    stanza: &str,
+    cache: &mut Option<PBKDFCache>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<Option<Vec<u8>>, EnprotError> {
+    match parse_pbkdf(stanza)? {
+        (pbkdfopts, Some(wrapped)) => {
+            unwrap_password(password, &pbkdfopts, &wrapped, cache, policy)
+        }
+        (_, None) => Err(EnprotError::Crypto("Password stanza without a wrapped key")),
+    }
+}
+
+// the key derived from the password, or unwrapped with it
+fn password_key(
+    password: &str,
+    key_len: usize,
+    extfields: &BTreeMap<String, String>,
+    cache: &mut Option<PBKDFCache>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<Vec<u8>, EnprotError> {
+    // the stanzas come first, a derived key only fails on the ciphertext
+    for (_, stanza) in password_stanzas(extfields) {
+        if let Some(key) = password_stanza_key(password, stanza, cache, policy)? {
+            return Ok(key);
+        }
+    }
+    if let Some(pbkdf) = extfields.get("pbkdf") {
+        match parse_pbkdf(pbkdf)? {
+            (pbkdfopts, Some(wrapped)) => {
+                unwrap_password(password, &pbkdfopts, &wrapped, cache, policy)?
+                    .ok_or(EnprotError::Crypto("Wrong password for the data key"))
+            }
+            (pbkdfopts, None) => {
+                Ok(derive_key(password, key_len, &None, &pbkdfopts, cache, policy)?.0)
+            }
+        }
+    } else if !password_stanzas(extfields).is_empty() && extfields.contains_key("version") {
+        // only a segment that predates data keys derives its key besides
+        Err(EnprotError::Crypto("Wrong password for the data key"))
+    } else if !password_protected(extfields) {
+        Err(EnprotError::Crypto("No password can decrypt this segment"))
+    } else {
+        let (thekey, _) = derive_key(password, key_len, &None, &legacy_pbkdf(), cache, policy)?;
+        Ok(thekey)
+    }
+}
+
+// the data key unwrapped by any of the identities
+fn identity_key(
+    identities: &[Vec<u8>],
+    extfields: &BTreeMap<String, String>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<Option<Vec<u8>>, EnprotError> {
+    for stanza in recipient_stanzas(extfields) {
+        for secret in identities {
+            if let Some(key) = recipient::unwrap(stanza, secret, policy)? {
+                return Ok(Some(key));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// decrypt with the password
+pub fn decrypt(
+    keyw: &str,
+    ct: Vec<u8>,
+    password: &str,
+    extfields: &BTreeMap<String, String>,
+    document_id: &Option<String>,
+    cache: &mut Option<PBKDFCache>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<Opened, EnprotError> {
+    let pt = open(
+        keyw,
+        &ct,
+        KeySource::Password(password),
+        extfields,
+        document_id,
+        cache,
+        policy,
+    )?;
+    pt.ok_or(EnprotError::Crypto("No password can decrypt this segment"))
+}
+
+// decrypt with an identity among the recipients, None if there is none
+pub fn decrypt_for_identities(
+    keyw: &str,
+    ct: &[u8],
+    identities: &[Vec<u8>],
+    extfields: &BTreeMap<String, String>,
+    document_id: &Option<String>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<Option<Opened>, EnprotError> {
+    if identities.is_empty() {
+        return Ok(None);
+    }
+    open(
+        keyw,
+        ct,
+        KeySource::Identities(identities),
+        extfields,
+        document_id,
+        &mut None,
+        policy,
+    )
+}
+
+// Access, changing the password stanzas without touching the ciphertext
+
+// wrap the data key for another password, in a new stanza with its own salt
+pub fn add_password(
+    key: &[u8],
+    password: &str,
+    extfields: &BTreeMap<String, String>,
+    rng: &Option<botan::RandomNumberGenerator>,
+    pbkdfopts: &etree::PBKDFOptions,
+    cache: &mut Option<PBKDFCache>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<BTreeMap<String, String>, EnprotError> {
+    let (kek, pbkdf) = derive_key(password, consts::KEK_LEN, rng, pbkdfopts, cache, policy)?;
+    let wrapped = utils::base64_encode(&cipher::wrap_key(&kek, key, policy)?)?;
+    let stanza = match pbkdf {
+        Some(pbkdf) => format!("{}${}", pbkdf, wrapped),
+        None => format!("{}{}", consts::LEGACY_STANZA, wrapped),
+    };
+    let mut extfields = extfields.clone();
+    let name = (0..)
+        .map(|i| format!("password.{}", i))
+        .find(|name| !extfields.contains_key(name))
+        .unwrap();
+    extfields.insert(name, stanza);
+    Ok(extfields)
+}
+
+// the extfields less the password stanzas the password opens
+fn drop_password(
+    password: &str,
+    extfields: &BTreeMap<String, String>,
+    cache: &mut Option<PBKDFCache>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<BTreeMap<String, String>, EnprotError> {
+    let mut remaining = extfields.clone();
+    for (name, stanza) in password_stanzas(extfields) {
+        if password_stanza_key(password, stanza, cache, policy)?.is_some() {
+            remaining.remove(name);
+        }
+    }
+    if remaining.len() == extfields.len() {
+        return Err(EnprotError::Crypto(
+            "The password opens no password stanza of the segment",
+        ));
+    }
+    Ok(remaining)
+}
+
+// drop the password stanzas the password opens, as long as another password
+// or a recipient can still decrypt the segment; the password of a segment
+// encrypted before data keys derives its key and is only replaced by rekey
+pub fn remove_password(
+    password: &str,
+    extfields: &BTreeMap<String, String>,
+    cache: &mut Option<PBKDFCache>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<BTreeMap<String, String>, EnprotError> {
+    let remaining = drop_password(password, extfields, cache, policy)?;
+    if !remaining.contains_key("pbkdf")
+        && password_stanzas(&remaining).is_empty()
+        && recipient_stanzas(&remaining).is_empty()
+    {
+        return Err(EnprotError::Crypto(
+            "Nothing else would decrypt the segment, use rekey to replace the password",
+        ));
+    }
+    Ok(remaining)
+}
+
+// wrap the data key for the new password in place of the stanzas the old one
+// opens, keeping those of other passwords and of the recipients
+#[allow(clippy::too_many_arguments)]
+pub fn replace_password(
+    key: &[u8],
+    old_password: &str,
+    new_password: &str,
+    extfields: &BTreeMap<String, String>,
+    rng: &Option<botan::RandomNumberGenerator>,
+    pbkdfopts: &etree::PBKDFOptions,
+    cache: &mut Option<PBKDFCache>,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<BTreeMap<String, String>, EnprotError> {
+    let remaining = drop_password(old_password, extfields, cache, policy)?;
+    add_password(key, new_password, &remaining, rng, pbkdfopts, cache, policy)
 }
@@ -0,0 +1,183 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use cipher;
+use consts;
+use crypto::CryptoPolicy;
+use error::EnprotError;
+use utils;
+
+// Public-key recipients of a segment. Each gets the data key wrapped under
+// a key agreed between a fresh ephemeral key and their X25519 public key,
+// recorded as a "x25519$EPHEMERAL$WRAPPED" stanza.
+
+const PUBLIC_PREFIX: &str = "x25519:";
+const SECRET_PREFIX: &str = "x25519-secret:";
+
+// "x25519:BASE64", as given to --recipient
+
+pub fn parse_public(text: &str) -> Result<Vec<u8>, EnprotError> {
+    text.strip_prefix(PUBLIC_PREFIX)
+        .and_then(|b64| utils::base64_decode(b64).ok())
+        .filter(|key| key.len() == 32)
+        .ok_or(EnprotError::Crypto("Not an x25519:BASE64 public key"))
+}
+
+pub fn format_public(key: &[u8]) -> Result<String, EnprotError> {
+    Ok(format!("{}{}", PUBLIC_PREFIX, utils::base64_encode(key)?))
+}
+
+// a new identity, as the text of an identity file and its public key
+
+pub fn generate(rng: &botan::RandomNumberGenerator) -> Result<(String, String), EnprotError> {
+    let botan_err = |_| EnprotError::Crypto("Botan error generating key");
+    let key = botan::Privkey::create("Curve25519", "", rng).map_err(botan_err)?;
+    let secret = key.get_x25519_key().map_err(botan_err)?;
+    let public = key
+        .pubkey()
+        .and_then(|key| key.get_x25519_key())
+        .map_err(botan_err)?;
+    let public = format_public(&public)?;
+    let identity = format!(
+        "# public key: {}\n{}{}\n",
+        public,
+        SECRET_PREFIX,
+        utils::base64_encode(&secret)?
+    );
+    Ok((identity, public))
+}
+
+// write a new identity file, readable by its owner only
+
+pub fn write_identity(path: &Path, identity: &str) -> Result<(), EnprotError> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(identity.as_bytes()))
+        .map_err(|io| EnprotError::Io {
+            path: path.display().to_string(),
+            io,
+        })
+}
+
+// the secret keys of an identity file, one per line, with # comments
+
+pub fn read_identities(path: &Path) -> Result<Vec<Vec<u8>>, EnprotError> {
+    let text = fs::read_to_string(path).map_err(|io| EnprotError::Io {
+        path: path.display().to_string(),
+        io,
+    })?;
+    let invalid = |msg| EnprotError::Identity {
+        path: path.display().to_string(),
+        msg,
+    };
+    let mut secrets = Vec::new();
+    for line in text.lines().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let secret = line
+            .strip_prefix(SECRET_PREFIX)
+            .and_then(|b64| utils::base64_decode(b64).ok())
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| invalid("not an x25519-secret:BASE64 line"))?;
+        secrets.push(secret);
+    }
+    if secrets.is_empty() {
+        return Err(invalid("no secret keys"));
+    }
+    Ok(secrets)
+}
+
+// the key-encryption key shared by an ephemeral and a recipient key
+
+fn agree(
+    secret: &[u8],
+    public: &[u8],
+    ephemeral: &[u8],
+    recipient: &[u8],
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<Vec<u8>, EnprotError> {
+    policy.check_key_agreement(consts::RECIPIENT_KEY_ALG)?;
+    let botan_err = |_| EnprotError::Crypto("Botan error in key agreement");
+    let key = botan::Privkey::load_x25519(secret).map_err(botan_err)?;
+    let salt = [ephemeral, recipient].concat();
+    botan::KeyAgreement::new(&key, consts::RECIPIENT_KDF)
+        .and_then(|ka| ka.agree(consts::KEK_LEN, public, &salt))
+        .map_err(botan_err)
+}
+
+// wrap a data key for a recipient public key
+
+pub fn wrap(
+    key: &[u8],
+    recipient: &[u8],
+    rng: &botan::RandomNumberGenerator,
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<String, EnprotError> {
+    let botan_err = |_| EnprotError::Crypto("Botan error generating key");
+    let ephemeral = botan::Privkey::create("Curve25519", "", rng).map_err(botan_err)?;
+    let secret = ephemeral.get_x25519_key().map_err(botan_err)?;
+    let public = ephemeral
+        .pubkey()
+        .and_then(|key| key.get_x25519_key())
+        .map_err(botan_err)?;
+    let kek = agree(&secret, recipient, &public, recipient, policy)?;
+    Ok(format!(
+        "{}${}${}",
+        consts::RECIPIENT_KEY_ALG,
+        utils::base64_encode(&public)?,
+        utils::base64_encode(&cipher::wrap_key(&kek, key, policy)?)?
+    ))
+}
+
+// unwrap the data key of a stanza, if it was made for this identity
+
+pub fn unwrap(
+    stanza: &str,
+    secret: &[u8],
+    policy: &Box<dyn CryptoPolicy>,
+) -> Result<Option<Vec<u8>>, EnprotError> {
+    let fields: Vec<&str> = stanza.split('$').collect();
+    if fields.len() != 3 || fields[0] != consts::RECIPIENT_KEY_ALG {
+        return Err(EnprotError::Crypto("Invalid recipient extfield"));
+    }
+    let ephemeral = utils::base64_decode(fields[1])?;
+    let wrapped = utils::base64_decode(fields[2])?;
+    let public = botan::Privkey::load_x25519(secret)
+        .and_then(|key| key.pubkey())
+        .and_then(|key| key.get_x25519_key())
+        .map_err(|_| EnprotError::Crypto("Botan error loading key"))?;
+    let kek = agree(secret, &ephemeral, &ephemeral, &public, policy)?;
+    Ok(cipher::unwrap_key(&kek, &wrapped, policy))
+}
@@ -0,0 +1,84 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::path::Path;
+
+// comment syntax presets, each selecting a pair of markup separators
+
+pub struct Syntax {
+    pub name: &'static str,
+    pub left_sep: &'static str,
+    pub right_sep: &'static str,
+    pub extensions: &'static [&'static str], // file extensions selected by "auto"
+}
+
+pub const SYNTAXES: &[Syntax] = &[
+    Syntax {
+        name: "c",
+        left_sep: "// <(",
+        right_sep: ")>",
+        extensions: &[
+            "adoc", "c", "cc", "cpp", "cs", "cxx", "go", "h", "hh", "hpp", "java", "js", "kt",
+            "php", "rs", "scala", "swift", "ts",
+        ],
+    },
+    Syntax {
+        name: "hash",
+        left_sep: "# <(",
+        right_sep: ")>",
+        extensions: &[
+            "bash", "cfg", "cmake", "conf", "mk", "pl", "py", "r", "rb", "sh", "toml", "yaml",
+            "yml", "zsh",
+        ],
+    },
+    Syntax {
+        name: "html",
+        left_sep: "<!-- <(",
+        right_sep: ")> -->",
+        extensions: &["htm", "html", "markdown", "md", "svg", "xhtml", "xml"],
+    },
+    Syntax {
+        name: "tex",
+        left_sep: "% <(",
+        right_sep: ")>",
+        extensions: &["bib", "cls", "ltx", "sty", "tex"],
+    },
+    Syntax {
+        name: "raw",
+        left_sep: "<(",
+        right_sep: ")>",
+        extensions: &["txt"],
+    },
+];
+
+pub fn by_name(name: &str) -> Option<&'static Syntax> {
+    SYNTAXES.iter().find(|syntax| syntax.name == name)
+}
+
+// pick the preset from the extension of a file name
+pub fn by_path(path: &str) -> Option<&'static Syntax> {
+    let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+    SYNTAXES
+        .iter()
+        .find(|syntax| syntax.extensions.contains(&ext.as_str()))
+}
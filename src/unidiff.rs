@@ -0,0 +1,133 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//	unified diffs for --dry-run
+
+use std::cmp;
+use std::fmt::Write;
+
+const CONTEXT: usize = 3;
+
+enum Op<'a> {
+    Same(&'a str),
+    Del(&'a str),
+    Add(&'a str),
+}
+
+// lines including their terminators, so a missing final newline shows up
+fn lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (end, _) in text.match_indices('\n') {
+        lines.push(&text[start..=end]);
+        start = end + 1;
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+// "start,count" as printed in hunk headers
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+// unified diff turning `old` into `new`, empty if they are the same
+
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines = lines(old);
+    let new_lines = lines(new);
+    let ops: Vec<Op> = diff::slice(&old_lines, &new_lines)
+        .into_iter()
+        .map(|r| match r {
+            diff::Result::Both(l, _) => Op::Same(l),
+            diff::Result::Left(l) => Op::Del(l),
+            diff::Result::Right(l) => Op::Add(l),
+        })
+        .collect();
+
+    // line numbers in both files before each op
+    let mut pos = Vec::with_capacity(ops.len() + 1);
+    let (mut o, mut n) = (0, 0);
+    for op in &ops {
+        pos.push((o, n));
+        match *op {
+            Op::Same(_) => {
+                o += 1;
+                n += 1;
+            }
+            Op::Del(_) => o += 1,
+            Op::Add(_) => n += 1,
+        }
+    }
+    pos.push((o, n));
+
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|&(_, op)| !matches!(*op, Op::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut k = 0;
+    while k < changes.len() {
+        // merge changes whose contexts would touch
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut end = changes[k] + 1;
+        k += 1;
+        while k < changes.len() && changes[k] <= end + 2 * CONTEXT {
+            end = changes[k] + 1;
+            k += 1;
+        }
+        let end = cmp::min(end + CONTEXT, ops.len());
+
+        let (o0, n0) = pos[start];
+        let (o1, n1) = pos[end];
+        writeln!(out, "@@ -{} +{} @@", range(o0, o1 - o0), range(n0, n1 - n0)).unwrap();
+        for op in &ops[start..end] {
+            match *op {
+                Op::Same(l) => push_line(&mut out, ' ', l),
+                Op::Del(l) => push_line(&mut out, '-', l),
+                Op::Add(l) => push_line(&mut out, '+', l),
+            }
+        }
+    }
+    out
+}
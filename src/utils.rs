@@ -21,10 +21,12 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-pub fn base64_encode(data: &[u8]) -> Result<String, &'static str> {
-    botan::base64_encode(data).map_err(|_| "Botan error")
+use error::EnprotError;
+
+pub fn base64_encode(data: &[u8]) -> Result<String, EnprotError> {
+    botan::base64_encode(data).map_err(|_| EnprotError::Crypto("Botan error"))
 }
 
-pub fn base64_decode(data: &str) -> Result<Vec<u8>, &'static str> {
-    botan::base64_decode(data).map_err(|_| "Botan error")
+pub fn base64_decode(data: &str) -> Result<Vec<u8>, EnprotError> {
+    botan::base64_decode(data).map_err(|_| EnprotError::Crypto("Botan error"))
 }
@@ -0,0 +1,59 @@
+// Copyright (c) 2020 [Ribose Inc](https://www.ribose.com).
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions
+// are met:
+// 1. Redistributions of source code must retain the above copyright
+//    notice, this list of conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright
+//    notice, this list of conditions and the following disclaimer in the
+//    documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// ``AS IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::fmt::Write;
+
+use document::{Check, CheckStatus};
+
+// report of the checks of a document, one line per segment
+
+pub fn text(path: &str, checks: &[Check]) -> String {
+    let mut out = format!("{}:\n", path);
+    text_checks(&mut out, checks, 1);
+    out
+}
+
+fn text_checks(out: &mut String, checks: &[Check], depth: usize) {
+    for check in checks {
+        write!(
+            out,
+            "{}{} {} lines {}-{}",
+            "  ".repeat(depth),
+            check.keyword,
+            check.state.name(),
+            check.lines.0,
+            check.lines.1
+        )
+        .unwrap();
+        if let Some(ref cas) = check.cas {
+            write!(out, " cas {}", cas).unwrap();
+        }
+        match check.status {
+            CheckStatus::Ok => out.push_str(": ok\n"),
+            CheckStatus::Authenticated => out.push_str(": ok, authenticated\n"),
+            CheckStatus::NoKey => out.push_str(": ok, not authenticated (no key)\n"),
+            CheckStatus::Failed(ref e) => writeln!(out, ": FAILED: {}", e).unwrap(),
+        }
+        text_checks(out, &check.children, depth + 1);
+    }
+}
@@ -0,0 +1,225 @@
+use enprot::crypto::CryptoPolicyDefault;
+use enprot::{Document, EnprotError, ParseOps, TextNode};
+use proptest::prelude::*;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use tempfile::tempdir;
+
+fn paops(casdir: &std::path::Path) -> ParseOps {
+    let mut paops = ParseOps::new(Box::new(CryptoPolicyDefault {}));
+    paops.casdir = casdir.to_path_buf();
+    paops
+}
+
+#[test]
+fn api_parse_inspect() {
+    let casdir = tempdir().unwrap();
+    let mut paops = paops(casdir.path());
+    let doc = Document::parse(
+        BufReader::new(File::open("sample/test.ept").unwrap()),
+        &mut paops,
+    )
+    .unwrap();
+    let keywords: Vec<&str> = doc
+        .nodes()
+        .iter()
+        .filter_map(|node| match node {
+            TextNode::BeginEnd { keyw, .. } => Some(keyw.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(keywords, vec!["GEHEIM", "Agent_007"]);
+}
+
+#[test]
+fn api_store_fetch_roundtrip() {
+    let casdir = tempdir().unwrap();
+    let mut paops = paops(casdir.path());
+    let doc = Document::parse(
+        BufReader::new(File::open("sample/test.ept").unwrap()),
+        &mut paops,
+    )
+    .unwrap();
+
+    // store
+    paops.store.insert("GEHEIM".to_string());
+    let stored = doc.transform(&mut paops).unwrap();
+    let mut buf = Vec::new();
+    stored.write(&mut buf, &mut paops).unwrap();
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        fs::read_to_string("test-data/test-store-geheim.ept").unwrap()
+    );
+
+    // fetch
+    paops.store.clear();
+    paops.fetch.insert("GEHEIM".to_string());
+    let fetched = stored.transform(&mut paops).unwrap();
+    let mut buf = Vec::new();
+    fetched.write(&mut buf, &mut paops).unwrap();
+    assert_eq!(buf, fs::read("sample/test.ept").unwrap());
+}
+
+#[test]
+fn api_error_source() {
+    let casdir = tempdir().unwrap();
+    let mut paops = paops(casdir.path());
+    let doc = Document::parse(
+        BufReader::new(File::open("test-data/test-encrypt-agent007.ept").unwrap()),
+        &mut paops,
+    )
+    .unwrap();
+    paops.decrypt.insert("Agent_007".to_string());
+    paops
+        .passwords
+        .insert("Agent_007".to_string(), "wrong".to_string());
+    let err = doc.transform(&mut paops).err().unwrap();
+    match err {
+        EnprotError::Decrypt { .. } => (),
+        ref other => panic!("unexpected error: {:?}", other),
+    }
+    // the cause can be walked down to as well
+    let source = err.source().unwrap();
+    assert!(err.to_string().ends_with(&source.to_string()));
+}
+
+fn eol() -> impl Strategy<Value = &'static str> {
+    prop_oneof![Just("\n"), Just("\r\n")]
+}
+
+fn indent() -> impl Strategy<Value = &'static str> {
+    prop_oneof![Just(""), Just("  "), Just("\t")]
+}
+
+fn trailer() -> impl Strategy<Value = &'static str> {
+    prop_oneof![Just(""), Just(" "), Just(" */")]
+}
+
+// whitespace between the separators and the directive
+fn spacing() -> impl Strategy<Value = &'static str> {
+    prop_oneof![Just(" "), Just("  "), Just("\t")]
+}
+
+// a line of arbitrary bytes that can't be mistaken for markup
+fn plain_line() -> impl Strategy<Value = Vec<u8>> {
+    (
+        prop::collection::vec(
+            any::<u8>().prop_filter("", |c| *c != b'\n' && *c != b'/'),
+            0..40,
+        ),
+        eol(),
+    )
+        .prop_map(|(mut line, eol)| {
+            line.extend_from_slice(eol.as_bytes());
+            line
+        })
+}
+
+// a DATA line of arbitrary length and layout
+fn data_line() -> impl Strategy<Value = Vec<u8>> {
+    (
+        indent(),
+        spacing(),
+        prop::collection::vec(any::<u8>(), 1..60),
+        spacing(),
+        trailer(),
+        eol(),
+    )
+        .prop_map(|(indent, pre, data, post, trailer, eol)| {
+            format!(
+                "{}// <({}DATA {}{})>{}{}",
+                indent,
+                pre,
+                enprot::utils::base64_encode(&data).unwrap(),
+                post,
+                trailer,
+                eol
+            )
+            .into_bytes()
+        })
+}
+
+fn segment() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        plain_line(),
+        (
+            indent(),
+            eol(),
+            prop::collection::vec(plain_line(), 0..4),
+            eol(),
+            trailer(),
+            any::<bool>(),
+            spacing(),
+        )
+            .prop_map(|(indent, eol, body, end_eol, trailer, split, space)| {
+                // the right separator may be on a later line
+                let sep = if split { eol } else { " " };
+                let mut seg = format!(
+                    "{}// <({}BEGIN{}X{})>{}{}",
+                    indent, space, sep, space, trailer, eol
+                )
+                .into_bytes();
+                for line in body {
+                    seg.extend(line);
+                }
+                seg.extend(format!("{}// <( END X )>{}", indent, end_eol).into_bytes());
+                seg
+            }),
+        (indent(), eol(), spacing()).prop_map(|(indent, eol, space)| format!(
+            "{}// <({}STORED X {} )>{}",
+            indent,
+            space,
+            "ab".repeat(32),
+            eol
+        )
+        .into_bytes()),
+        (
+            indent(),
+            eol(),
+            spacing(),
+            prop::collection::vec(data_line(), 1..4),
+            eol(),
+        )
+            .prop_map(|(indent, eol, space, data, end_eol)| {
+                let mut seg =
+                    format!("{}// <({}ENCRYPTED X{})>{}", indent, space, space, eol).into_bytes();
+                for line in data {
+                    seg.extend(line);
+                }
+                seg.extend(format!("{}// <( END X )>{}", indent, end_eol).into_bytes());
+                seg
+            }),
+    ]
+}
+
+proptest! {
+    // a no-op transform must reproduce its input byte for byte
+    #[test]
+    fn api_noop_roundtrip(
+        bom in any::<bool>(),
+        segments in prop::collection::vec(segment(), 0..8),
+        final_eol in any::<bool>(),
+    ) {
+        let mut input = if bom { b"\xef\xbb\xbf".to_vec() } else { Vec::new() };
+        for seg in segments {
+            input.extend(seg);
+        }
+        if !final_eol {
+            if input.ends_with(b"\r\n") {
+                input.truncate(input.len() - 2);
+            } else if input.ends_with(b"\n") {
+                input.truncate(input.len() - 1);
+            }
+        }
+
+        let casdir = tempdir().unwrap();
+        let mut paops = paops(casdir.path());
+        let doc = Document::parse(&input[..], &mut paops).unwrap();
+        let doc = doc.transform(&mut paops).unwrap();
+        let mut output = Vec::new();
+        doc.write(&mut output, &mut paops).unwrap();
+        prop_assert_eq!(output, input);
+    }
+}
@@ -0,0 +1,361 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+fn enprot(casdir: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("-c").arg(casdir);
+    cmd
+}
+
+fn access(casdir: &Path, op: &str) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.args(["access", op, "Agent_007", "-c"]).arg(casdir);
+    cmd
+}
+
+fn data_lines(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter(|line| line.contains("DATA"))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn assert_decrypts(casdir: &Path, ept: &Fixture, password: &str, ok: bool) {
+    let encrypted = fs::read_to_string(&ept.path).unwrap();
+    let assert = enprot(casdir)
+        .args(["-d", "Agent_007", "-k"])
+        .arg(format!("Agent_007={}", password))
+        .arg(&ept.path)
+        .assert();
+    if ok {
+        assert.success();
+        assert_eq!(
+            &fs::read_to_string(&ept.path).unwrap(),
+            &fs::read_to_string(&ept.source).unwrap()
+        );
+    } else {
+        assert.failure();
+    }
+    fs::write(&ept.path, &encrypted).unwrap();
+}
+
+#[test]
+fn access_add_remove() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=one"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let data = data_lines(&ept.path);
+
+    access(casdir.path(), "add")
+        .args(["-k", "Agent_007=one", "-n", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    access(casdir.path(), "add")
+        .args(["-k", "Agent_007=two", "-n", "Agent_007=three"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let text = fs::read_to_string(&ept.path).unwrap();
+    for stanza in &["password.0:$", "password.1:$", "password.2:$"] {
+        assert!(text.contains(stanza));
+    }
+    // the ciphertext itself is left alone
+    assert_eq!(data_lines(&ept.path), data);
+    for password in &["one", "two", "three"] {
+        assert_decrypts(casdir.path(), &ept, password, true);
+    }
+    assert_decrypts(casdir.path(), &ept, "four", false);
+
+    access(casdir.path(), "remove")
+        .args(["-k", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(data_lines(&ept.path), data);
+    assert_decrypts(casdir.path(), &ept, "two", false);
+    assert_decrypts(casdir.path(), &ept, "one", true);
+    assert_decrypts(casdir.path(), &ept, "three", true);
+
+    access(casdir.path(), "remove")
+        .args(["-k", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The password opens no password stanza of the segment",
+        ));
+}
+
+#[test]
+fn access_remove_first() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=alice"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let data = data_lines(&ept.path);
+    access(casdir.path(), "add")
+        .args(["-k", "Agent_007=alice", "-n", "Agent_007=bob"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    access(casdir.path(), "remove")
+        .args(["-k", "Agent_007=alice"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(data_lines(&ept.path), data);
+    assert_decrypts(casdir.path(), &ept, "alice", false);
+    assert_decrypts(casdir.path(), &ept, "bob", true);
+
+    // the last password stays
+    access(casdir.path(), "remove")
+        .args(["-k", "Agent_007=bob"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Nothing else would decrypt the segment",
+        ));
+    assert_decrypts(casdir.path(), &ept, "bob", true);
+}
+
+#[test]
+fn access_old_segment() {
+    let casdir = tempdir().unwrap();
+    // segments encrypted under a key derived from the password
+    for legacy in &[
+        "test-data/test-encrypt-agent007.ept",
+        "test-data/test-encrypt-agent007-scrypt.ept",
+        "test-data/test-encrypt-agent007-scrypt-v2-derived.ept",
+    ] {
+        let ept = Fixture::copy(legacy);
+        let data = data_lines(&ept.path);
+        access(casdir.path(), "add")
+            .args(["-k", "Agent_007=password", "-n", "Agent_007=two"])
+            .arg(&ept.path)
+            .assert()
+            .success();
+        assert_eq!(data_lines(&ept.path), data);
+        for password in &["password", "two"] {
+            let encrypted = fs::read_to_string(&ept.path).unwrap();
+            enprot(casdir.path())
+                .args(["-d", "Agent_007", "-k"])
+                .arg(format!("Agent_007={}", password))
+                .arg(&ept.path)
+                .assert()
+                .success();
+            assert_eq!(
+                &fs::read_to_string(&ept.path).unwrap(),
+                &fs::read_to_string("sample/test.ept").unwrap()
+            );
+            fs::write(&ept.path, &encrypted).unwrap();
+        }
+
+        // the password the key is derived from is only replaced by rekey
+        access(casdir.path(), "remove")
+            .args(["-k", "Agent_007=password"])
+            .arg(&ept.path)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "The password opens no password stanza of the segment",
+            ));
+    }
+}
+
+#[test]
+fn access_add_wrong_password() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=one"])
+        .args(["--cipher", "aes-256-gcm"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let encrypted = fs::read_to_string(&ept.path).unwrap();
+    access(casdir.path(), "add")
+        .args(["-k", "Agent_007=wrong", "-n", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Error decrypting Agent_007: Wrong password for the data key",
+        ));
+    assert_eq!(fs::read_to_string(&ept.path).unwrap(), encrypted);
+}
+
+#[test]
+fn access_stored() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-E", "Agent_007", "-k", "Agent_007=one"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let stored = fs::read_to_string(&ept.path).unwrap();
+    access(casdir.path(), "add")
+        .args(["-k", "Agent_007=one", "-n", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    // the reference to the ciphertext doesn't change
+    let added = fs::read_to_string(&ept.path).unwrap();
+    let reference = |text: &str| {
+        text.lines()
+            .find(|line| line.contains("ENCRYPTED"))
+            .and_then(|line| line.split_whitespace().nth(3))
+            .map(|cas| cas.to_string())
+    };
+    assert!(reference(&added).is_some());
+    assert_eq!(reference(&added), reference(&stored));
+    assert_decrypts(casdir.path(), &ept, "two", true);
+}
+
+#[test]
+fn access_recipient() {
+    let casdir = tempdir().unwrap();
+    let keys = tempdir().unwrap();
+    let id = keys.path().join("id");
+    let output = Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["keygen", "-o"])
+        .arg(&id)
+        .output()
+        .unwrap();
+    let public = String::from_utf8(output.stdout).unwrap();
+
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "--recipient", public.trim()])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    access(casdir.path(), "add")
+        .arg("-i")
+        .arg(&id)
+        .args(["-n", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_decrypts(casdir.path(), &ept, "two", true);
+
+    // the identity still decrypts once the password is gone
+    access(casdir.path(), "remove")
+        .args(["-k", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_decrypts(casdir.path(), &ept, "two", false);
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-i"])
+        .arg(&id)
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn access_rekey_keeps_stanzas() {
+    let casdir = tempdir().unwrap();
+    let keys = tempdir().unwrap();
+    let id = keys.path().join("id");
+    let output = Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["keygen", "-o"])
+        .arg(&id)
+        .output()
+        .unwrap();
+    let public = String::from_utf8(output.stdout).unwrap();
+
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args([
+            "-e",
+            "Agent_007",
+            "-k",
+            "Agent_007=one",
+            "--recipient",
+            public.trim(),
+        ])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    access(casdir.path(), "add")
+        .args(["-k", "Agent_007=one", "-n", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let data = data_lines(&ept.path);
+
+    // only the stanza of the old password is replaced
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["rekey", "Agent_007", "-c"])
+        .arg(casdir.path())
+        .args(["-k", "Agent_007=one", "-n", "Agent_007=three"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(data_lines(&ept.path), data);
+    assert_decrypts(casdir.path(), &ept, "one", false);
+    assert_decrypts(casdir.path(), &ept, "two", true);
+    assert_decrypts(casdir.path(), &ept, "three", true);
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-i"])
+        .arg(&id)
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn access_rekey_old_segment() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("test-data/test-encrypt-agent007.ept");
+    access(casdir.path(), "add")
+        .args(["-k", "Agent_007=password", "-n", "Agent_007=two"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let encrypted = fs::read_to_string(&ept.path).unwrap();
+
+    // re-encrypting under a new data key would lock out the added password
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["rekey", "Agent_007", "-c"])
+        .arg(casdir.path())
+        .args(["-k", "Agent_007=password", "-n", "Agent_007=three"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Rekey would drop the other passwords of a segment without a data key",
+        ));
+    assert_eq!(fs::read_to_string(&ept.path).unwrap(), encrypted);
+}
@@ -0,0 +1,191 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+const AGENT_007: &str = "575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306";
+const GEHEIM: &str = "af24962e45e3a7486189769ccda19167ac0b199aca28a5122ab3d647eec7d893";
+const JAMES_BOND: &str = "d094e230861eb0ab43b895b8ecdeeb9e3a7e4a88239341a81da832ac181feaab";
+
+// test.ept with GEHEIM stored, its blob referring to the nested Agent_007
+fn stored(casdir: &Path) -> Fixture {
+    let ept = Fixture::copy("sample/test.ept");
+    for word in &["Agent_007", "GEHEIM"] {
+        Command::cargo_bin("enprot")
+            .unwrap()
+            .arg("-c")
+            .arg(casdir)
+            .arg("-s")
+            .arg(word)
+            .arg(&ept.path)
+            .assert()
+            .success();
+    }
+    ept
+}
+
+fn export(casdir: &Path, ept: &Path, bundle: &Path) {
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["bundle", "export", "-c"])
+        .arg(casdir)
+        .arg("-o")
+        .arg(bundle)
+        .arg(ept)
+        .assert()
+        .success();
+}
+
+fn import(casdir: &Path, bundle: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.args(["bundle", "import", "-c"]).arg(casdir).arg(bundle);
+    cmd
+}
+
+#[test]
+fn bundle_round_trip() {
+    let mine = tempdir().unwrap();
+    let theirs = tempdir().unwrap();
+    let ept = stored(mine.path());
+    // not referenced by the document
+    fs::write(mine.path().join("e".repeat(64)), "unrelated").unwrap();
+    let bundle = mine.path().join("test.bundle");
+    export(mine.path(), &ept.path, &bundle);
+
+    import(theirs.path(), &bundle)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 objects for"));
+    let mut names = fs::read_dir(theirs.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<String>>();
+    names.sort();
+    assert_eq!(names, vec![AGENT_007, GEHEIM, JAMES_BOND]);
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(theirs.path())
+        .arg("-f")
+        .arg("GEHEIM,Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn bundle_rejects_tampering() {
+    let mine = tempdir().unwrap();
+    let theirs = tempdir().unwrap();
+    let ept = stored(mine.path());
+    let bundle = mine.path().join("test.bundle");
+    export(mine.path(), &ept.path, &bundle);
+
+    let data = fs::read(&bundle).unwrap();
+    let at = data
+        .windows(19)
+        .position(|w| w == b"Super secret line 3")
+        .unwrap();
+    let mut tampered = data.clone();
+    tampered[at + 18] = b'4';
+    fs::write(&bundle, &tampered).unwrap();
+
+    import(theirs.path(), &bundle)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "object {} has contents hashing to",
+            AGENT_007
+        )));
+    // nothing is imported from a bad bundle
+    assert_eq!(fs::read_dir(theirs.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn bundle_missing_object() {
+    let mine = tempdir().unwrap();
+    let ept = stored(mine.path());
+    fs::remove_file(mine.path().join(JAMES_BOND)).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["bundle", "export", "-c"])
+        .arg(mine.path())
+        .arg("-o")
+        .arg(mine.path().join("test.bundle"))
+        .arg(&ept.path)
+        .assert()
+        .failure();
+    assert!(!mine.path().join("test.bundle").exists());
+}
+
+#[test]
+fn bundle_pipe() {
+    let mine = tempdir().unwrap();
+    let theirs = tempdir().unwrap();
+    let ept = stored(mine.path());
+
+    let output = Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["bundle", "export", "-o", "-", "-c"])
+        .arg(mine.path())
+        .arg(&ept.path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    import(theirs.path(), Path::new("-"))
+        .with_stdin()
+        .buffer(output.stdout)
+        .assert()
+        .success();
+    assert!(theirs.path().join(JAMES_BOND).is_file());
+}
+
+#[test]
+fn bundle_round_trip_hashes() {
+    let mine = tempdir().unwrap();
+    let theirs = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    for (word, alg) in &[("Agent_007", "sha256"), ("GEHEIM", "blake2b")] {
+        Command::cargo_bin("enprot")
+            .unwrap()
+            .arg("-c")
+            .arg(mine.path())
+            .args(["--cas-hash", alg, "-s", word])
+            .arg(&ept.path)
+            .assert()
+            .success();
+    }
+    let bundle = mine.path().join("test.bundle");
+    export(mine.path(), &ept.path, &bundle);
+    let data = String::from_utf8_lossy(&fs::read(&bundle).unwrap()).into_owned();
+    assert!(data.contains("hashes = [\n    \"blake2b\",\n    \"sha256\",\n]\n"));
+
+    import(theirs.path(), &bundle)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 objects for"));
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(theirs.path())
+        .arg("-f")
+        .arg("GEHEIM,Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
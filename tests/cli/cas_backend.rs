@@ -0,0 +1,233 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tempfile::tempdir;
+
+use Fixture;
+
+type Objects = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+// a minimal CAS server under /cas/, one request per connection
+fn serve() -> (String, Objects) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/cas/", listener.local_addr().unwrap());
+    let objects = Objects::default();
+    let shared = objects.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            handle(stream.unwrap(), &shared);
+        }
+    });
+    (url, objects)
+}
+
+fn handle(stream: TcpStream, objects: &Objects) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request = String::new();
+    reader.read_line(&mut request).unwrap();
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap();
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+
+    let mut parts = request.split_whitespace();
+    let method = parts.next().unwrap();
+    let id = parts
+        .next()
+        .unwrap()
+        .trim_start_matches("/cas/")
+        .to_string();
+    let mut objects = objects.lock().unwrap();
+    let (status, reply) = match (method, objects.get(&id)) {
+        ("GET", _) if id.is_empty() => {
+            let list: Vec<&str> = objects.keys().map(|id| id.as_str()).collect();
+            ("200 OK", (list.join("\n") + "\n").into_bytes())
+        }
+        ("GET", Some(data)) => ("200 OK", data.clone()),
+        ("HEAD", Some(_)) => ("200 OK", Vec::new()),
+        ("PUT", _) => {
+            objects.insert(id, body);
+            ("201 Created", Vec::new())
+        }
+        ("DELETE", Some(_)) => {
+            objects.remove(&id);
+            ("204 No Content", Vec::new())
+        }
+        _ => ("404 Not Found", Vec::new()),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n",
+        status,
+        reply.len()
+    )
+    .unwrap();
+    if method != "HEAD" {
+        stream.write_all(&reply).unwrap();
+    }
+}
+
+// store and fetch Agent_007 through the given --cas URL
+fn store_fetch(url: &str) {
+    let ept = Fixture::copy("sample/test.ept");
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("--cas")
+        .arg(url)
+        .arg("-s")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string("test-data/test-store-agent007.ept").unwrap()
+    );
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("verify")
+        .arg("--cas")
+        .arg(url)
+        .arg(&ept.path)
+        .assert()
+        .success();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("ls")
+        .arg("--cas")
+        .arg(url)
+        .arg(&ept.path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Agent_007 stored lines 7-7 \
+             cas 575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306 size 20\n",
+        ));
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("--cas")
+        .arg(url)
+        .arg("-f")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn cas_backend_file() {
+    let casdir = tempdir().unwrap();
+
+    store_fetch(&format!("file:{}", casdir.path().display()));
+    assert_eq!(
+        &fs::read_to_string(
+            casdir
+                .path()
+                .join("575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306")
+        )
+        .unwrap(),
+        "Super secret line 3\n"
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn cas_backend_sqlite() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("secrets.db");
+
+    store_fetch(&format!("sqlite:{}", db.display()));
+    assert!(db.is_file());
+    // nothing else is written
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn cas_backend_http() {
+    let (url, objects) = serve();
+
+    store_fetch(&url);
+    let objects = objects.lock().unwrap();
+    assert_eq!(objects.len(), 2);
+    assert_eq!(
+        objects["575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306"],
+        b"Super secret line 3\n".to_vec()
+    );
+    assert_eq!(
+        objects["d094e230861eb0ab43b895b8ecdeeb9e3a7e4a88239341a81da832ac181feaab"],
+        b"James Bond\n".to_vec()
+    );
+}
+
+#[test]
+fn cas_backend_http_invalid_listing() {
+    let (url, objects) = serve();
+    objects
+        .lock()
+        .unwrap()
+        .insert("../../etc/passwd".to_string(), Vec::new());
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["gc", "--delete", "--cas", &url, "sample/test.ept"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid object name '../../etc/passwd' in listing",
+        ));
+    assert_eq!(objects.lock().unwrap().len(), 1);
+}
+
+#[cfg(not(feature = "sqlite"))]
+#[test]
+fn cas_backend_sqlite_missing() {
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["--cas", "sqlite:secrets.db", "-s", "Agent_007"])
+        .args(["sample/test.ept", "-o", "-"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "built without SQLite support (cargo feature \"sqlite\")",
+        ));
+}
+
+#[test]
+fn cas_backend_invalid_url() {
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("--cas")
+        .arg("ftp://example.com/cas/")
+        .arg("-s")
+        .arg("Agent_007")
+        .arg("sample/test.ept")
+        .arg("-o")
+        .arg("-")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid CAS URL ftp://example.com/cas/: expected a file:, sqlite: or http:// URL",
+        ));
+}
@@ -0,0 +1,204 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+const AGENT_007: &str = "575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306";
+const GEHEIM: &str = "af24962e45e3a7486189769ccda19167ac0b199aca28a5122ab3d647eec7d893";
+
+fn store(cas: &[&str]) {
+    let ept = Fixture::copy("sample/test.ept");
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(cas)
+        .arg("-s")
+        .arg("Agent_007")
+        .arg("-s")
+        .arg("GEHEIM")
+        .arg(&ept.path)
+        .assert()
+        .success();
+}
+
+fn fsck(casdir: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("cas").arg("fsck").arg("-c").arg(casdir);
+    cmd
+}
+
+#[test]
+fn cas_fsck_clean() {
+    let casdir = tempdir().unwrap();
+    store(&["-c", casdir.path().to_str().unwrap()]);
+    fsck(casdir.path()).assert().success().stdout("");
+}
+
+#[test]
+fn cas_fsck_problems() {
+    let casdir = tempdir().unwrap();
+    store(&["-c", casdir.path().to_str().unwrap()]);
+    let empty = "e".repeat(64);
+    fs::write(casdir.path().join(AGENT_007), "corrupted").unwrap();
+    fs::write(casdir.path().join(&empty), "").unwrap();
+    // anything but objects is left alone
+    fs::write(casdir.path().join("notes.txt"), "stray").unwrap();
+    fs::write(casdir.path().join(".x.123.0.tmp"), "partial").unwrap();
+    fs::create_dir(casdir.path().join("src")).unwrap();
+
+    fsck(casdir.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(format!(
+            "{}: contents hash to ",
+            AGENT_007
+        )))
+        .stdout(predicate::str::contains(format!("{}: empty object", empty)))
+        .stdout(predicate::str::contains("notes.txt").not())
+        .stdout(predicate::str::contains(".x.123.0.tmp").not())
+        .stdout(predicate::str::contains("src:").not())
+        .stdout(predicate::str::contains(GEHEIM).not())
+        .stderr(predicate::str::contains("4 objects checked, 2 problems"));
+
+    // nothing is touched without --quarantine
+    assert!(casdir.path().join(AGENT_007).is_file());
+    assert!(!casdir.path().join("lost+found").exists());
+}
+
+#[test]
+fn cas_fsck_quarantine() {
+    let casdir = tempdir().unwrap();
+    store(&["-c", casdir.path().to_str().unwrap()]);
+    fs::write(casdir.path().join(AGENT_007), "corrupted").unwrap();
+    fs::write(casdir.path().join("notes.txt"), "stray").unwrap();
+
+    fsck(casdir.path())
+        .arg("--quarantine")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("moved to"));
+    let lost_found = casdir.path().join("lost+found");
+    assert_eq!(
+        fs::read_to_string(lost_found.join(AGENT_007)).unwrap(),
+        "corrupted"
+    );
+    assert!(casdir.path().join("notes.txt").is_file());
+    assert!(!casdir.path().join(AGENT_007).exists());
+    assert!(casdir.path().join(GEHEIM).is_file());
+
+    // now it is clean, and quarantined files don't count
+    fsck(casdir.path()).assert().success().stdout("");
+
+    // a second quarantined copy doesn't replace the first
+    fs::write(casdir.path().join(AGENT_007), "corrupted again").unwrap();
+    fsck(casdir.path()).arg("--quarantine").assert().success();
+    assert_eq!(
+        fs::read_to_string(lost_found.join(format!("{}.1", AGENT_007))).unwrap(),
+        "corrupted again"
+    );
+}
+
+#[test]
+fn cas_fsck_sharded() {
+    let casdir = tempdir().unwrap();
+    store(&[
+        "-c",
+        casdir.path().to_str().unwrap(),
+        "--cas-layout",
+        "sharded",
+    ]);
+    let shard = casdir.path().join(&AGENT_007[..2]);
+    fs::write(shard.join(&AGENT_007[2..]), "corrupted").unwrap();
+    fs::write(shard.join("abc"), "stray").unwrap();
+
+    fsck(casdir.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("contents hash to"))
+        .stdout(predicate::str::contains("abc").not());
+    fsck(casdir.path()).arg("--quarantine").assert().success();
+    assert!(casdir.path().join("lost+found").join(AGENT_007).is_file());
+    assert!(shard.join("abc").is_file());
+}
+
+#[test]
+fn cas_fsck_unmarked() {
+    // a working tree, where the casdir defaults to the current directory
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("notes.txt"), "stray").unwrap();
+    fs::write(dir.path().join("e".repeat(64)), "").unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["cas", "fsck", "--no-config", "--quarantine"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "has no .enprot-cas marker, name it with -c to check it anyway",
+        ));
+    assert!(!dir.path().join("lost+found").exists());
+
+    // marked, as by a sharded layout
+    store(&[
+        "-c",
+        dir.path().to_str().unwrap(),
+        "--cas-layout",
+        "sharded",
+    ]);
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["cas", "fsck", "--no-config", "--quarantine"])
+        .assert()
+        .success();
+    assert!(dir.path().join("notes.txt").is_file());
+    assert!(dir.path().join("lost+found").join("e".repeat(64)).is_file());
+}
+
+#[test]
+fn cas_fsck_configured() {
+    // a casdir set in .enprot.toml is as deliberate as one named with -c
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("cas")).unwrap();
+    fs::write(dir.path().join(".enprot.toml"), "casdir = \"cas\"\n").unwrap();
+    fs::write(dir.path().join("cas").join("e".repeat(64)), "").unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["cas", "fsck", "--quarantine"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("empty object, moved to"));
+    assert!(dir
+        .path()
+        .join("cas")
+        .join("lost+found")
+        .join("e".repeat(64))
+        .is_file());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn cas_fsck_sqlite() {
+    let dir = tempdir().unwrap();
+    let url = format!("sqlite:{}", dir.path().join("cas.db").display());
+    store(&["--cas", &url]);
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["cas", "fsck", "--cas", &url])
+        .assert()
+        .success()
+        .stdout("");
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["cas", "fsck", "--quarantine", "--cas", &url])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Can't quarantine"));
+}
@@ -0,0 +1,141 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+// the CAS identifiers in a document, in order
+fn cas_ids(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter(|line| line.contains("STORED") || line.contains("ENCRYPTED"))
+        .map(|line| line.split_whitespace().nth(4).unwrap().to_string())
+        .collect()
+}
+
+fn enprot(casdir: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("-c").arg(casdir);
+    cmd
+}
+
+#[test]
+fn cas_hash_prefixed_ids() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["--cas-hash", "sha3-512", "-s", "Agent_007"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let ids = cas_ids(&ept.path);
+    let digest = ids[0].strip_prefix("sha3-512:").unwrap();
+    assert_eq!(digest.len(), 128);
+    assert!(casdir.path().join(format!("{}.sha3-512", digest)).is_file());
+
+    // the identifier alone tells how to check the object
+    enprot(casdir.path())
+        .args(["-f", "Agent_007"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn cas_hash_encrypted_store() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    enprot(casdir.path())
+        .args(["--cas-layout", "sharded", "--cas-hash", "blake2b"])
+        .args(["-E", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let ids = cas_ids(&ept.path);
+    assert_eq!(ids.len(), 2);
+    assert!(ids.iter().all(|id| id.starts_with("blake2b:")));
+
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["cas", "fsck", "-v", "-c"])
+        .arg(casdir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("2 objects checked, 0 problems"));
+}
+
+#[test]
+fn cas_hash_config() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("cas")).unwrap();
+    fs::write(
+        dir.path().join(".enprot.toml"),
+        "casdir = \"cas\"\ncas-hash = \"sha256\"\n",
+    )
+    .unwrap();
+    let ept = dir.path().join("simple.ept");
+    fs::copy("sample/simple.ept", &ept).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["store", "Agent_007"])
+        .arg(&ept)
+        .assert()
+        .success();
+    assert!(cas_ids(&ept)[0].starts_with("sha256:"));
+}
+
+#[test]
+fn cas_hash_policy() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args([
+            "--policy",
+            "nist",
+            "--cas-hash",
+            "sha256",
+            "-s",
+            "Agent_007",
+        ])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Hash algorithm is not permitted by policy: sha256",
+        ));
+}
+
+#[test]
+fn cas_hash_invalid_id() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::blank("sample/simple.ept");
+    fs::write(
+        &ept.path,
+        "// <( STORED Agent_007 sha256:575d69f5b0034279bc3ef164e94287e6 )>\n",
+    )
+    .unwrap();
+    enprot(casdir.path())
+        .args(["-f", "Agent_007"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Not a valid CAS identifier"));
+}
@@ -0,0 +1,174 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+const AGENT_007: &str = "575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306";
+
+fn enprot(casdir: &Path, key: Option<&Path>) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("-c").arg(casdir);
+    if let Some(key) = key {
+        cmd.arg("--cas-key-file").arg(key);
+    }
+    cmd
+}
+
+// the single STORED identifier in a document
+fn stored_id(path: &Path) -> String {
+    let text = fs::read_to_string(path).unwrap();
+    let line = text.lines().find(|line| line.contains("STORED")).unwrap();
+    line.split_whitespace().nth(4).unwrap().to_string()
+}
+
+#[test]
+fn cas_key_hides_hash() {
+    let casdir = tempdir().unwrap();
+    let keydir = tempdir().unwrap();
+    let key = keydir.path().join("cas.key");
+    fs::write(&key, "0123456789abcdef0123456789abcdef\n").unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    let again = Fixture::copy("sample/simple.ept");
+
+    for fixture in &[&ept, &again] {
+        enprot(casdir.path(), Some(&key))
+            .arg("-s")
+            .arg("Agent_007")
+            .arg(&fixture.path)
+            .assert()
+            .success();
+    }
+    let id = stored_id(&ept.path);
+    assert_eq!(id, stored_id(&again.path));
+    assert!(casdir.path().join(&id).is_file());
+
+    // the same contents unkeyed get another name
+    let unkeyed = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path(), None)
+        .arg("-s")
+        .arg("Agent_007")
+        .arg(&unkeyed.path)
+        .assert()
+        .success();
+    assert_ne!(id, stored_id(&unkeyed.path));
+
+    // fetching needs the key
+    enprot(casdir.path(), None)
+        .arg("-f")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("CAS content hash mismatch"));
+    enprot(casdir.path(), Some(&key))
+        .arg("-f")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn cas_key_reads_plain_objects() {
+    let casdir = tempdir().unwrap();
+    let keydir = tempdir().unwrap();
+    let key = keydir.path().join("cas.key");
+    fs::write(&key, "0123456789abcdef0123456789abcdef").unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    enprot(casdir.path(), None)
+        .arg("-s")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert!(casdir.path().join(AGENT_007).is_file());
+
+    enprot(casdir.path(), Some(&key))
+        .arg("-f")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["cas", "fsck", "-c"])
+        .arg(casdir.path())
+        .arg("--cas-key-file")
+        .arg(&key)
+        .assert()
+        .success();
+}
+
+#[test]
+fn cas_key_config() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("cas")).unwrap();
+    fs::write(
+        dir.path().join("cas.key"),
+        "0123456789abcdef0123456789abcdef",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join(".enprot.toml"),
+        "casdir = \"cas\"\ncas-key-file = \"cas.key\"\n",
+    )
+    .unwrap();
+    let ept = dir.path().join("simple.ept");
+    fs::copy("sample/simple.ept", &ept).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("store")
+        .arg("--trust-config")
+        .arg("Agent_007")
+        .arg(&ept)
+        .assert()
+        .success();
+    let id = stored_id(&ept);
+    assert!(dir.path().join("cas").join(&id).is_file());
+
+    // fsck and bundles check keyed names too
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["cas", "fsck", "--trust-config", "-c", "cas"])
+        .assert()
+        .success()
+        .stdout("");
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["cas", "fsck", "--no-config", "-c", "cas"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(format!(
+            "{}: contents hash to",
+            id
+        )));
+}
+
+#[test]
+fn cas_key_too_short() {
+    let casdir = tempdir().unwrap();
+    let keydir = tempdir().unwrap();
+    let key = keydir.path().join("cas.key");
+    fs::write(&key, "secret\n").unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path(), Some(&key))
+        .arg("-s")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "CAS key must be at least 16 bytes",
+        ));
+}
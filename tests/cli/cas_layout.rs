@@ -0,0 +1,128 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+#[test]
+fn cas_layout_sharded() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("--cas-layout")
+        .arg("sharded")
+        .arg("-s")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(casdir.path().join(".enprot-cas")).unwrap(),
+        "layout = \"sharded\"\n"
+    );
+    assert_eq!(
+        &fs::read_to_string(
+            casdir
+                .path()
+                .join("57/5d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306")
+        )
+        .unwrap(),
+        "Super secret line 3\n"
+    );
+    assert_eq!(
+        &fs::read_to_string(
+            casdir
+                .path()
+                .join("d0/94e230861eb0ab43b895b8ecdeeb9e3a7e4a88239341a81da832ac181feaab")
+        )
+        .unwrap(),
+        "James Bond\n"
+    );
+
+    // the marker decides the layout from now on
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-f")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn cas_layout_mixed() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+
+    // a flat store, switched to the sharded layout afterwards
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-s")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert!(casdir
+        .path()
+        .join("575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306")
+        .is_file());
+    fs::write(casdir.path().join(".enprot-cas"), "layout = \"sharded\"\n").unwrap();
+
+    // new objects are sharded, old ones are still found
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-s")
+        .arg("GEHEIM")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(fs::read_dir(casdir.path()).unwrap().count(), 4);
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-f")
+        .arg("GEHEIM,Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn cas_layout_invalid_marker() {
+    let casdir = tempdir().unwrap();
+    fs::write(casdir.path().join(".enprot-cas"), "layout = \"deep\"\n").unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-s")
+        .arg("Agent_007")
+        .arg("sample/test.ept")
+        .arg("-o")
+        .arg("-")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown layout 'deep'"));
+}
@@ -0,0 +1,92 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+const AGENT_007: &str = "575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306";
+
+fn store(casdirs: &[&Path], extra: &[&str], ept: &Fixture) {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    for casdir in casdirs {
+        cmd.arg("-c").arg(casdir);
+    }
+    cmd.args(extra)
+        .arg("-s")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+}
+
+#[test]
+fn cas_search_read_fallback() {
+    let personal = tempdir().unwrap();
+    let shared = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    store(&[shared.path()], &[], &ept);
+
+    // found in the second casdir, given as a list
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(format!(
+            "{}:{}",
+            personal.path().display(),
+            shared.path().display()
+        ))
+        .arg("-v")
+        .arg("-f")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(format!(
+            "of {} from file:{}",
+            AGENT_007,
+            shared.path().display()
+        )));
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+    assert_eq!(fs::read_dir(personal.path()).unwrap().count(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn cas_search_first_writable() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let shared = tempdir().unwrap();
+    let personal = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    fs::set_permissions(shared.path(), fs::Permissions::from_mode(0o555)).unwrap();
+    // the permissions don't hold back root
+    if fs::File::create(shared.path().join("probe")).is_ok() {
+        return;
+    }
+
+    store(&[shared.path(), personal.path()], &[], &ept);
+    fs::set_permissions(shared.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(fs::read_dir(shared.path()).unwrap().count(), 0);
+    assert!(personal.path().join(AGENT_007).is_file());
+}
+
+#[test]
+fn cas_search_write_casdir() {
+    let first = tempdir().unwrap();
+    let second = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+
+    store(
+        &[first.path(), second.path()],
+        &["--write-casdir", second.path().to_str().unwrap()],
+        &ept,
+    );
+    assert_eq!(fs::read_dir(first.path()).unwrap().count(), 0);
+    assert!(second.path().join(AGENT_007).is_file());
+}
@@ -4,6 +4,8 @@ use std::fs;
 use std::process::Command;
 
 use Fixture;
+use DATA_KEY;
+use DATA_KEY_SIV;
 
 #[test]
 fn encrypt_gcm_random_iv() {
@@ -78,6 +80,8 @@ fn encrypt_gcm() {
         .arg("aes-256-gcm")
         .arg("--cipher-iv")
         .arg("010203040506070809101112")
+        .arg("--data-key")
+        .arg(DATA_KEY)
         .arg(&ept.path)
         .assert()
         .success();
@@ -86,7 +90,7 @@ fn encrypt_gcm() {
         .contains("aes-256-gcm"));
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/simple-encrypt-agent007-gcm.ept").unwrap()
+        &fs::read_to_string("test-data/simple-encrypt-agent007-gcm-v2.ept").unwrap()
     );
     // decrypt
     Command::cargo_bin("enprot")
@@ -146,12 +150,14 @@ fn encrypt_siv() {
         .arg("Agent_007=password")
         .arg("--cipher")
         .arg("aes-256-siv")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/simple-encrypt-agent007.ept").unwrap(),
+        &fs::read_to_string("test-data/simple-encrypt-agent007-v2.ept").unwrap(),
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -188,6 +194,8 @@ fn encrypt_gcm_siv() {
         .arg("aes-256-gcm-siv")
         .arg("--cipher-iv")
         .arg("010203040506070809101112")
+        .arg("--data-key")
+        .arg(DATA_KEY)
         .arg(&ept.path)
         .assert()
         .success();
@@ -196,7 +204,7 @@ fn encrypt_gcm_siv() {
         .contains("aes-256-gcm-siv"));
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/simple-encrypt-agent007-gcm-siv.ept").unwrap()
+        &fs::read_to_string("test-data/simple-encrypt-agent007-gcm-siv-v2.ept").unwrap()
     );
     // decrypt
     Command::cargo_bin("enprot")
@@ -0,0 +1,229 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+const BLOCK: &str = "// <( BEGIN Agent_007 )>\nsecret\n// <( END Agent_007 )>\n";
+
+// a repository with the configuration at its root, readable by its owner only
+fn setup(config: &str) -> tempfile::TempDir {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    fs::write(dir.path().join(".enprot.toml"), config).unwrap();
+    chmod(&dir.path().join(".enprot.toml"), 0o600);
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    dir
+}
+
+fn chmod(path: &Path, mode: u32) {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn encrypted(path: &Path, left: &str) -> bool {
+    fs::read_to_string(path)
+        .unwrap()
+        .starts_with(&format!("{} ENCRYPTED Agent_007 ", left))
+}
+
+#[test]
+fn config_discovered_from_parent() {
+    let dir = setup(
+        r#"
+casdir = "cas"
+encrypt-store = ["Agent_007"]
+
+[keys]
+Agent_007 = "password"
+
+[pbkdf]
+alg = "legacy"
+"#,
+    );
+    fs::create_dir(dir.path().join("cas")).unwrap();
+    let path = dir.path().join("sub").join("file.ept");
+    fs::write(&path, BLOCK).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-v")
+        .arg(&path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Using configuration"))
+        .stderr(predicate::str::contains(".enprot.toml"));
+    assert!(encrypted(&path, "// <("));
+    // the casdir is relative to the configuration file
+    assert_eq!(fs::read_dir(dir.path().join("cas")).unwrap().count(), 1);
+}
+
+#[test]
+fn config_separator_overrides() {
+    let dir = setup(
+        r#"
+left-separator = "{"
+right-separator = "}"
+encrypt = ["Agent_007"]
+
+[keys]
+Agent_007 = "password"
+
+[[separators]]
+glob = "*.py"
+syntax = "hash"
+"#,
+    );
+    let braces = dir.path().join("sub").join("file.ept");
+    fs::write(&braces, "{ BEGIN Agent_007 }\nsecret\n{ END Agent_007 }\n").unwrap();
+    let python = dir.path().join("sub").join("file.py");
+    fs::write(&python, BLOCK.replace("//", "#")).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg(&braces)
+        .arg(&python)
+        .assert()
+        .success();
+    assert!(encrypted(&braces, "{"));
+    assert!(encrypted(&python, "# <("));
+}
+
+#[test]
+fn config_command_line_precedence() {
+    let dir = setup(
+        r#"
+left-separator = "{"
+right-separator = "}"
+encrypt = ["Other"]
+"#,
+    );
+    let path = dir.path().join("sub").join("file.ept");
+    fs::write(&path, BLOCK).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-l")
+        .arg("// <(")
+        .arg("-r")
+        .arg(")>")
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg("-e")
+        .arg("Agent_007")
+        .arg(&path)
+        .assert()
+        .success();
+    assert!(encrypted(&path, "// <("));
+}
+
+#[test]
+fn config_ignored() {
+    let dir = setup("encrypt = [\"Agent_007\"]\n");
+    let path = dir.path().join("sub").join("file.ept");
+    fs::write(&path, BLOCK).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("--no-config")
+        .arg(&path)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&path).unwrap(), BLOCK);
+}
+
+#[test]
+fn config_invalid() {
+    for (config, err) in [
+        ("bogus = 1\n", "unknown key 'bogus'"),
+        (
+            "[pbkdf]\nalg = \"md5\"\n",
+            "'md5' isn't a valid value for 'alg'",
+        ),
+        ("max-depth = \"deep\"\n", "'max-depth' must be a number"),
+        ("encrypt = \n", "Invalid configuration"),
+    ]
+    .iter()
+    {
+        let dir = setup(config);
+        let path = dir.path().join("sub").join("file.ept");
+        fs::write(&path, BLOCK).unwrap();
+
+        Command::cargo_bin("enprot")
+            .unwrap()
+            .arg(&path)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid configuration"))
+            .stderr(predicate::str::contains(*err));
+    }
+}
+
+#[test]
+fn config_lookup_stops_at_repository() {
+    let dir = setup("encrypt = [\"Agent_007\"]\n");
+    // a nested repository without a configuration of its own
+    let repo = dir.path().join("sub");
+    fs::create_dir(repo.join(".git")).unwrap();
+    fs::create_dir(repo.join("src")).unwrap();
+    let path = repo.join("src").join("file.ept");
+    fs::write(&path, BLOCK).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-v")
+        .arg(&path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("No configuration file found"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), BLOCK);
+}
+
+#[test]
+fn config_untrusted() {
+    for (config, key) in [
+        ("cas = \"http://localhost:1/cas\"\n", "cas"),
+        ("cas = \"file:/tmp/cas\"\n", "cas"),
+        ("cas-key-file = \"cas.key\"\n", "cas-key-file"),
+        (
+            "recipients = [\"x25519:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\"]\n",
+            "recipients",
+        ),
+        ("[pbkdf]\nsalt = \"00112233\"\n", "pbkdf.salt"),
+        ("[cipher]\niv = \"00112233\"\n", "cipher.iv"),
+    ]
+    .iter()
+    {
+        let dir = setup(config);
+        let path = dir.path().join("sub").join("file.ept");
+        fs::write(&path, BLOCK).unwrap();
+
+        Command::cargo_bin("enprot")
+            .unwrap()
+            .arg(&path)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "'{}' is only accepted with --trust-config",
+                key
+            )));
+    }
+}
+
+#[test]
+fn config_keys_owner_only() {
+    let dir = setup("[keys]\nAgent_007 = \"password\"\n");
+    chmod(&dir.path().join(".enprot.toml"), 0o644);
+    let path = dir.path().join("sub").join("file.ept");
+    fs::write(&path, BLOCK).unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["-e", "Agent_007"])
+        .arg(&path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("readable by its owner only"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), BLOCK);
+}
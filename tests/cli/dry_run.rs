@@ -0,0 +1,95 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+#[test]
+fn dry_run_store() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    let path = ept.path.to_str().unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("store")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("--dry-run")
+        .arg(path)
+        .assert()
+        .code(2)
+        .stdout(format!(
+            "--- {0}
++++ {0}
+@@ -2,10 +2,6 @@
+ // <( BEGIN GEHEIM )>
+ Secret line 1
+ Secret line 2
+-// <( BEGIN Agent_007 )>
+-James Bond
+-// <( END Agent_007 )>
++// <( STORED Agent_007 d094e230861eb0ab43b895b8ecdeeb9e3a7e4a88239341a81da832ac181feaab )>
+ // <( END GEHEIM )>
+-// <( BEGIN Agent_007 )>
+-Super secret line 3
+-// <( END Agent_007 )>
++// <( STORED Agent_007 575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306 )>
+",
+            path
+        ));
+    // nothing was written
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+    assert_eq!(fs::read_dir(casdir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn dry_run_unchanged() {
+    let ept = Fixture::copy("sample/test.ept");
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("decrypt")
+        .arg("Agent_007")
+        .arg("--dry-run")
+        .arg(&ept.path)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn dry_run_no_final_newline() {
+    let casdir = tempdir().unwrap();
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-s")
+        .arg("X")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("--dry-run")
+        .with_stdin()
+        .buffer("a\n// <( BEGIN X )>\nb\n// <( END X )>\nc")
+        .assert()
+        .code(2)
+        .stdout(
+            "--- <stdin>
++++ -
+@@ -1,5 +1,3 @@
+ a
+-// <( BEGIN X )>
+-b
+-// <( END X )>
++// <( STORED X 006ef4138df934503f34702cfc24b743664b78635dd65844413d464e2867729c )>
+ c
+\\ No newline at end of file
+",
+        );
+}
@@ -5,6 +5,7 @@ use std::process::Command;
 use tempfile::tempdir;
 
 use Fixture;
+use DATA_KEY_SIV;
 
 #[test]
 fn encrypt_decrypt_agent007() {
@@ -21,12 +22,14 @@ fn encrypt_decrypt_agent007() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -59,6 +62,8 @@ fn encrypt_decrypt_agent007_stdin_pass() {
         .arg("Agent_007")
         .arg("--pbkdf")
         .arg("legacy")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .with_stdin()
         .buffer("password\r\npassword\r\n")
@@ -66,7 +71,7 @@ fn encrypt_decrypt_agent007_stdin_pass() {
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -100,12 +105,14 @@ fn encrypt_decrypt_both_agent007_geheim() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password,GEHEIM=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-geheim-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-geheim-agent007-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -143,12 +150,14 @@ fn encrypt_decrypt_geheim_agent007() {
         .arg("legacy")
         .arg("-k")
         .arg("GEHEIM=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-geheim.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-geheim-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -160,12 +169,14 @@ fn encrypt_decrypt_geheim_agent007() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-geheim-then-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-geheim-then-agent007-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -180,7 +191,7 @@ fn encrypt_decrypt_geheim_agent007() {
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-geheim.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-geheim-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -214,12 +225,14 @@ fn encrypt_decrypt_agent007_geheim() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -231,12 +244,14 @@ fn encrypt_decrypt_agent007_geheim() {
         .arg("legacy")
         .arg("-k")
         .arg("GEHEIM=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007-geheim.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-geheim-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -251,7 +266,7 @@ fn encrypt_decrypt_agent007_geheim() {
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -291,7 +306,7 @@ fn encrypt_decrypt_agent007_default_pbkdf() {
     // just make sure it selected argon2
     assert!(&fs::read_to_string(&ept.path)
         .unwrap()
-        .contains("pbkdf:$argon2$"));
+        .contains("password.0:$argon2$"));
     Command::cargo_bin("enprot")
         .unwrap()
         .arg("-c")
@@ -328,12 +343,14 @@ fn encrypt_decrypt_agent007_pbkdf2() {
         .arg("0102030405060708")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007-pbkdf2.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-pbkdf2-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -371,12 +388,14 @@ fn encrypt_decrypt_agent007_pbkdf2_sha512() {
         .arg("0102030405060708")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007-pbkdf2-sha512.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-pbkdf2-sha512-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -465,12 +484,14 @@ fn encrypt_decrypt_agent007_scrypt() {
         .arg("0102030405060708")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007-scrypt.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-scrypt-v2.ept").unwrap()
     );
     Command::cargo_bin("enprot")
         .unwrap()
@@ -5,6 +5,7 @@ use tempfile::tempdir;
 
 use hexdigest;
 use Fixture;
+use DATA_KEY_SIV;
 
 #[test]
 fn encrypt_store_agent007() {
@@ -21,16 +22,18 @@ fn encrypt_store_agent007() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-store-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-store-agent007-v2.ept").unwrap()
     );
     for hashval in vec![
-        "1749eaec9b40e6757ddd29b58002b01cf210e46119a8345421de80c6a3dd672a",
-        "7a8da017c0fe671ba16f4bc55b884444e708849290d8366f19c552c90950b8c2",
+        "e345149a1b11fa0ae8d6a399a0c99baef9b3ade4f330cc026fc66833b8b6acd6",
+        "e45281035346768bf71d84d825a38a7d685d044d4b8b37478536780b382ab31b",
     ] {
         assert_eq!(
             hexdigest("sha3-256", &fs::read(casdir.path().join(hashval)).unwrap(),).unwrap(),
@@ -69,14 +72,16 @@ fn encrypt_store_geheim() {
         .arg("legacy")
         .arg("-k")
         .arg("GEHEIM=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-store-geheim.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-store-geheim-v2.ept").unwrap()
     );
-    for hashval in vec!["ab664af9ef8ed0a7a542c4bcc0d2d2bf06973038d83ddbfcdd031eb80a308d5a"] {
+    for hashval in vec!["24e57491f47885d78e9afbee4df5edcbb14a75958faac1063fd71d40b945023c"] {
         assert_eq!(
             hexdigest("sha3-256", &fs::read(casdir.path().join(hashval)).unwrap(),).unwrap(),
             hashval
@@ -114,17 +119,19 @@ fn encrypt_store_both_agent007_geheim() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password,GEHEIM=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-store-both.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-store-both-v2.ept").unwrap()
     );
     for hashval in vec![
-        "7a8da017c0fe671ba16f4bc55b884444e708849290d8366f19c552c90950b8c2",
-        "1749eaec9b40e6757ddd29b58002b01cf210e46119a8345421de80c6a3dd672a",
-        "86117980a54565a74cc5195865827aab44cfafc138e723e3a409631384d74ee2",
+        "e45281035346768bf71d84d825a38a7d685d044d4b8b37478536780b382ab31b",
+        "e345149a1b11fa0ae8d6a399a0c99baef9b3ade4f330cc026fc66833b8b6acd6",
+        "b9fdc9fcfaaf5457fb33849d7871e83ec418912366ab3071ee0baae1f612d699",
     ] {
         assert_eq!(
             hexdigest("sha3-256", &fs::read(casdir.path().join(hashval)).unwrap()).unwrap(),
@@ -163,16 +170,18 @@ fn encrypt_store_agent007_geheim() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-store-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-store-agent007-v2.ept").unwrap()
     );
     for hashval in vec![
-        "1749eaec9b40e6757ddd29b58002b01cf210e46119a8345421de80c6a3dd672a",
-        "7a8da017c0fe671ba16f4bc55b884444e708849290d8366f19c552c90950b8c2",
+        "e345149a1b11fa0ae8d6a399a0c99baef9b3ade4f330cc026fc66833b8b6acd6",
+        "e45281035346768bf71d84d825a38a7d685d044d4b8b37478536780b382ab31b",
     ] {
         assert_eq!(
             hexdigest("sha3-256", &fs::read(casdir.path().join(hashval)).unwrap()).unwrap(),
@@ -189,14 +198,16 @@ fn encrypt_store_agent007_geheim() {
         .arg("legacy")
         .arg("-k")
         .arg("GEHEIM=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-store-agent007-geheim.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-store-agent007-geheim-v2.ept").unwrap()
     );
-    for hashval in vec!["86117980a54565a74cc5195865827aab44cfafc138e723e3a409631384d74ee2"] {
+    for hashval in vec!["b9fdc9fcfaaf5457fb33849d7871e83ec418912366ab3071ee0baae1f612d699"] {
         assert_eq!(
             hexdigest("sha3-256", &fs::read(casdir.path().join(hashval)).unwrap()).unwrap(),
             hashval
@@ -246,14 +257,16 @@ fn encrypt_store_geheim_agent007() {
         .arg("legacy")
         .arg("-k")
         .arg("GEHEIM=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-store-geheim.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-store-geheim-v2.ept").unwrap()
     );
-    for hashval in vec!["ab664af9ef8ed0a7a542c4bcc0d2d2bf06973038d83ddbfcdd031eb80a308d5a"] {
+    for hashval in vec!["24e57491f47885d78e9afbee4df5edcbb14a75958faac1063fd71d40b945023c"] {
         assert_eq!(
             hexdigest("sha3-256", &fs::read(casdir.path().join(hashval)).unwrap()).unwrap(),
             hashval
@@ -269,14 +282,16 @@ fn encrypt_store_geheim_agent007() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-store-geheim-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-store-geheim-agent007-v2.ept").unwrap()
     );
-    for hashval in vec!["7a8da017c0fe671ba16f4bc55b884444e708849290d8366f19c552c90950b8c2"] {
+    for hashval in vec!["e45281035346768bf71d84d825a38a7d685d044d4b8b37478536780b382ab31b"] {
         assert_eq!(
             hexdigest("sha3-256", &fs::read(casdir.path().join(hashval)).unwrap()).unwrap(),
             hashval
@@ -329,16 +344,18 @@ fn encrypt_store_agent007_argon2() {
         .arg("0102030405060708")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .assert()
         .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-store-agent007-argon2.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-store-agent007-argon2-v2.ept").unwrap()
     );
     for hashval in vec![
-        "03596e1743f8d7e969979d5e4f9f8bf41bca02c723f84a2f12193b2196077805",
-        "b30ccd443bae74afc464822857fad6974f0cbb12197368494cc311441c74ea20",
+        "e345149a1b11fa0ae8d6a399a0c99baef9b3ade4f330cc026fc66833b8b6acd6",
+        "e45281035346768bf71d84d825a38a7d685d044d4b8b37478536780b382ab31b",
     ] {
         assert_eq!(
             hexdigest("sha3-256", &fs::read(casdir.path().join(hashval)).unwrap()).unwrap(),
@@ -0,0 +1,225 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+fn enprot(casdir: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("-c").arg(casdir);
+    cmd
+}
+
+fn edit(path: &Path, from: &str, to: &str) {
+    let text = fs::read_to_string(path).unwrap().replace(from, to);
+    fs::write(path, text).unwrap();
+}
+
+// Segments written before version 2, or before data keys, with the CAS
+// objects of the stored ones in test-data/cas-v1
+const LEGACY: &[(&str, &str)] = &[
+    ("test-encrypt-agent007", "sample/test.ept"),
+    ("test-encrypt-agent007-pbkdf2", "sample/test.ept"),
+    ("test-encrypt-agent007-pbkdf2-sha512", "sample/test.ept"),
+    ("test-encrypt-agent007-scrypt", "sample/test.ept"),
+    ("test-encrypt-agent007-scrypt-v2-derived", "sample/test.ept"),
+    ("test-encrypt-geheim", "sample/test.ept"),
+    ("test-encrypt-agent007-geheim", "sample/test.ept"),
+    ("test-encrypt-geheim-agent007", "sample/test.ept"),
+    ("test-encrypt-geheim-then-agent007", "sample/test.ept"),
+    ("test-encrypt-store-agent007", "sample/test.ept"),
+    ("test-encrypt-store-agent007-argon2", "sample/test.ept"),
+    ("test-encrypt-store-geheim", "sample/test.ept"),
+    ("test-encrypt-store-both", "sample/test.ept"),
+    ("test-encrypt-store-agent007-geheim", "sample/test.ept"),
+    ("test-encrypt-store-geheim-agent007", "sample/test.ept"),
+    ("simple-encrypt-agent007", "sample/simple.ept"),
+    ("simple-encrypt-agent007-gcm", "sample/simple.ept"),
+    ("simple-encrypt-agent007-gcm-siv", "sample/simple.ept"),
+    ("issue-15-encrypt-alice", "test-data/issue-15.ept"),
+    ("issue-15-encrypt-bob", "test-data/issue-15.ept"),
+    ("issue-15-encrypt-alice-bob", "test-data/issue-15.ept"),
+];
+const LEGACY_WORDS: &str = "Agent_007,GEHEIM,alice,bob";
+const LEGACY_KEYS: &str = "Agent_007=password,GEHEIM=password,alice=alicepass,bob=bobpass";
+
+#[test]
+fn format_version_legacy_decrypt() {
+    let casdir = tempdir().unwrap();
+    for entry in fs::read_dir("test-data/cas-v1").unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), casdir.path().join(entry.file_name())).unwrap();
+    }
+    for (legacy, plain) in LEGACY {
+        let ept = Fixture::copy(&format!("test-data/{}.ept", legacy));
+        enprot(casdir.path())
+            .args(["-d", LEGACY_WORDS, "-k", LEGACY_KEYS])
+            .arg(&ept.path)
+            .assert()
+            .success();
+        assert_eq!(
+            &fs::read_to_string(&ept.path).unwrap(),
+            &fs::read_to_string(plain).unwrap(),
+            "{}",
+            legacy
+        );
+    }
+}
+
+#[test]
+fn format_version_legacy_rekey() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("test-data/test-encrypt-agent007-scrypt.ept");
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["rekey", "Agent_007", "-c"])
+        .arg(casdir.path())
+        .args(["-k", "Agent_007=password", "-n", "Agent_007=secret"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let rekeyed = fs::read_to_string(&ept.path).unwrap();
+    assert!(rekeyed.contains("password.0:$") && rekeyed.contains("version:2"));
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=secret"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string("sample/test.ept").unwrap()
+    );
+}
+
+#[test]
+fn format_version_binds_keyword() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&ept.path).unwrap().contains("version:2"));
+
+    // the same password under another keyword doesn't help
+    edit(&ept.path, "Agent_007", "Agent_008");
+    enprot(casdir.path())
+        .args(["-d", "Agent_008", "-k", "Agent_008=password"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Error decrypting Agent_008"));
+}
+
+#[test]
+fn format_version_binds_extfields() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=password"])
+        .args(["--cipher", "aes-256-gcm"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let encrypted = fs::read_to_string(&ept.path).unwrap();
+
+    // dropping the version would downgrade to unauthenticated extfields
+    edit(&ept.path, " version:2", "");
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .failure();
+
+    fs::write(&ept.path, &encrypted).unwrap();
+    edit(&ept.path, "version:2", "version:3");
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported format version"));
+}
+
+#[test]
+fn format_version_document_id() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=password"])
+        .args(["--document-id", "report-1"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    // the id isn't recorded in the document
+    assert!(!fs::read_to_string(&ept.path).unwrap().contains("report-1"));
+
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .failure();
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .args(["--document-id", "report-2"])
+        .arg(&ept.path)
+        .assert()
+        .failure();
+
+    fs::write(
+        ept.path.parent().unwrap().join(".enprot.toml"),
+        "document-id = \"report-1\"\n",
+    )
+    .unwrap();
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn format_version_store_fetch_ciphertext() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=password"])
+        .args(["--cipher", "aes-256-gcm"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let encrypted = fs::read_to_string(&ept.path).unwrap();
+
+    // the extfields stay with the ciphertext wherever it is kept
+    enprot(casdir.path())
+        .args(["-s", "Agent_007"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let stored = fs::read_to_string(&ept.path).unwrap();
+    assert!(stored.contains("version:2") && stored.contains("cipher:aes-256-gcm"));
+    enprot(casdir.path())
+        .args(["-f", "Agent_007"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(&fs::read_to_string(&ept.path).unwrap(), &encrypted);
+
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
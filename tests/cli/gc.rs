@@ -0,0 +1,172 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+// Agent_007 as nested in GEHEIM, only referenced from within its CAS blob
+const JAMES_BOND: &str = "d094e230861eb0ab43b895b8ecdeeb9e3a7e4a88239341a81da832ac181feaab";
+
+fn enprot(casdir: &Path, args: &[&str], ept: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("-c").arg(casdir).args(args).arg(ept);
+    cmd
+}
+
+// stores test.ept with the GEHEIM blob referencing another, plus one garbage object
+fn setup(casdir: &Path) -> (Fixture, String) {
+    let ept = Fixture::copy("sample/test.ept");
+    enprot(casdir, &["-s", "Agent_007"], &ept.path)
+        .assert()
+        .success();
+    enprot(casdir, &["-s", "GEHEIM"], &ept.path)
+        .assert()
+        .success();
+    assert!(casdir.join(JAMES_BOND).is_file());
+
+    let simple = Fixture::copy("sample/simple.ept");
+    enprot(casdir, &["-s", "Agent_007"], &simple.path)
+        .assert()
+        .success();
+    let stored = fs::read_to_string(&simple.path).unwrap();
+    let garbage = stored
+        .lines()
+        .find(|line| line.contains("STORED"))
+        .and_then(|line| line.split_whitespace().nth(4))
+        .unwrap()
+        .to_string();
+    (ept, garbage)
+}
+
+#[test]
+fn gc_lists_unreachable() {
+    let casdir = tempdir().unwrap();
+    let (ept, garbage) = setup(casdir.path());
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("gc")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(ept.path.parent().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("unreachable {}", garbage)))
+        .stdout(predicate::str::contains(JAMES_BOND).not())
+        .stdout(predicate::str::contains("1 of 4 objects"));
+    assert!(casdir.path().join(&garbage).is_file());
+}
+
+#[test]
+fn gc_delete() {
+    let casdir = tempdir().unwrap();
+    let (ept, garbage) = setup(casdir.path());
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("gc")
+        .arg("--delete")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(&ept.path)
+        .assert()
+        .success()
+        .stdout(format!("deleted {}\n", garbage));
+    assert!(!casdir.path().join(&garbage).exists());
+    assert_eq!(fs::read_dir(casdir.path()).unwrap().count(), 3);
+
+    // everything needed is still there
+    enprot(casdir.path(), &["-f", "GEHEIM,Agent_007"], &ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn gc_refuses_unexamined() {
+    let casdir = tempdir().unwrap();
+    let (ept, garbage) = setup(casdir.path());
+    let dir = ept.path.parent().unwrap();
+    let secret = dir.join("secret.ept");
+    fs::copy("sample/simple.ept", &secret).unwrap();
+    enprot(
+        casdir.path(),
+        &["-e", "Agent_007", "-k", "Agent_007=pw"],
+        &secret,
+    )
+    .assert()
+    .success();
+
+    // the ciphertext could hide references
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("gc")
+        .arg("--delete")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Not deleting anything"))
+        .stderr(predicate::str::contains(
+            "secret.ept: Agent_007 lines 2-4: no key",
+        ));
+    assert!(casdir.path().join(&garbage).is_file());
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("gc")
+        .arg("--delete")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-k")
+        .arg("Agent_007=pw")
+        .arg(dir)
+        .assert()
+        .success()
+        .stdout(format!("deleted {}\n", garbage));
+}
+
+#[test]
+fn gc_only_write_casdir() {
+    let casdir = tempdir().unwrap();
+    let (ept, garbage) = setup(casdir.path());
+    // a shared store searched first, holding a copy of the garbage
+    let shared = tempdir().unwrap();
+    fs::copy(casdir.path().join(&garbage), shared.path().join(&garbage)).unwrap();
+
+    let gc = |delete: bool| {
+        let mut cmd = Command::cargo_bin("enprot").unwrap();
+        cmd.arg("gc")
+            .arg("-c")
+            .arg(shared.path())
+            .arg("-c")
+            .arg(casdir.path())
+            .arg("--write-casdir")
+            .arg(casdir.path());
+        if delete {
+            cmd.arg("--delete");
+        }
+        cmd.arg(ept.path.parent().unwrap());
+        cmd
+    };
+    gc(false)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "1 of 4 objects in file:{} unreachable",
+            casdir.path().display()
+        )));
+    gc(true)
+        .assert()
+        .success()
+        .stdout(format!("deleted {}\n", garbage));
+    assert!(!casdir.path().join(&garbage).exists());
+    assert!(shared.path().join(&garbage).is_file());
+}
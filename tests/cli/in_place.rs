@@ -0,0 +1,105 @@
+use assert_cmd::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+fn store(args: &[&str], ept: &Fixture) {
+    let casdir = tempdir().unwrap();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-s")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .args(args)
+        .arg(&ept.path)
+        .assert()
+        .success();
+}
+
+// the file names in the directory of a fixture
+fn dir_entries(ept: &Fixture) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(ept.path.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn in_place_no_leftovers() {
+    let ept = Fixture::copy("sample/test.ept");
+
+    store(&[], &ept);
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string("test-data/test-store-agent007.ept").unwrap()
+    );
+    assert_eq!(dir_entries(&ept), vec!["test.ept"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn in_place_preserves_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let ept = Fixture::copy("sample/test.ept");
+    fs::set_permissions(&ept.path, fs::Permissions::from_mode(0o640)).unwrap();
+
+    store(&[], &ept);
+    assert_eq!(
+        fs::metadata(&ept.path).unwrap().permissions().mode() & 0o777,
+        0o640
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn in_place_skips_unchanged() {
+    use std::os::unix::fs::MetadataExt;
+
+    let ept = Fixture::copy("sample/test.ept");
+    let ino = fs::metadata(&ept.path).unwrap().ino();
+
+    // nothing to fetch, so the file is neither replaced nor backed up
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-f")
+        .arg("Agent_007")
+        .arg("--backup")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(fs::metadata(&ept.path).unwrap().ino(), ino);
+    assert_eq!(dir_entries(&ept), vec!["test.ept"]);
+
+    store(&[], &ept);
+    assert_ne!(fs::metadata(&ept.path).unwrap().ino(), ino);
+}
+
+#[test]
+fn in_place_backup() {
+    let ept = Fixture::copy("sample/test.ept");
+
+    store(&["--backup"], &ept);
+    assert_eq!(dir_entries(&ept), vec!["test.ept", "test.ept~"]);
+    assert_eq!(
+        &fs::read_to_string(ept.path.with_file_name("test.ept~")).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+
+    let ept = Fixture::copy("sample/test.ept");
+    store(&["--backup=.orig"], &ept);
+    assert_eq!(dir_entries(&ept), vec!["test.ept", "test.ept.orig"]);
+    assert_eq!(
+        &fs::read_to_string(ept.path.with_file_name("test.ept.orig")).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string("test-data/test-store-agent007.ept").unwrap()
+    );
+}
@@ -4,6 +4,7 @@ use std::fs;
 use std::process::Command;
 
 use Fixture;
+use DATA_KEY_SIV;
 
 fn encdec(args: &[&str]) -> assert_cmd::assert::Assert {
     let mut asdf = Command::cargo_bin("enprot").unwrap();
@@ -19,6 +20,8 @@ fn encdec(args: &[&str]) -> assert_cmd::assert::Assert {
         .arg("0102030405060708")
         .arg("--cipher")
         .arg("aes-256-siv")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .args(args)
         .assert()
 }
@@ -37,7 +40,7 @@ fn nested_encrypt_alice() {
     .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/issue-15-encrypt-alice.ept").unwrap()
+        &fs::read_to_string("test-data/issue-15-encrypt-alice-v2.ept").unwrap()
     );
     // decrypt
     encdec(&[
@@ -68,7 +71,7 @@ fn nested_encrypt_bob() {
     .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/issue-15-encrypt-bob.ept").unwrap()
+        &fs::read_to_string("test-data/issue-15-encrypt-bob-v2.ept").unwrap()
     );
     // decrypt
     encdec(&[
@@ -99,7 +102,7 @@ fn nested_encrypt_alice_bob() {
     .success();
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/issue-15-encrypt-alice-bob.ept").unwrap()
+        &fs::read_to_string("test-data/issue-15-encrypt-alice-bob-v2.ept").unwrap()
     );
     // decrypt (all at once)
     encdec(&[
@@ -0,0 +1,103 @@
+use assert_cmd::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+#[test]
+fn ls_plain() {
+    let casdir = tempdir().unwrap();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("ls")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("sample/test.ept")
+        .assert()
+        .success()
+        .stdout(
+            "sample/test.ept:\n\
+             \x20 GEHEIM plain lines 2-8\n\
+             \x20   Agent_007 plain lines 5-7\n\
+             \x20 Agent_007 plain lines 9-11\n",
+        );
+}
+
+#[test]
+fn ls_encrypted_inline() {
+    let casdir = tempdir().unwrap();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("ls")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("test-data/simple-encrypt-agent007-gcm-v2.ept")
+        .assert()
+        .success()
+        .stdout(
+            "test-data/simple-encrypt-agent007-gcm-v2.ept:\n\
+             \x20 Agent_007 encrypted-inline lines 2-4 size 28 \
+             pbkdf argon2 (m=16,p=1,t=1) cipher aes-256-gcm\n",
+        );
+}
+
+#[test]
+fn ls_json() {
+    let casdir = tempdir().unwrap();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("ls")
+        .arg("--json")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("test-data/test-encrypt-store-agent007-argon2.ept")
+        .assert()
+        .success()
+        .stdout(concat!(
+            r#"[{"file":"test-data/test-encrypt-store-agent007-argon2.ept","segments":["#,
+            r#"{"keyword":"GEHEIM","state":"plain","lines":[2,6],"segments":["#,
+            r#"{"keyword":"Agent_007","state":"encrypted-cas","lines":[5,5],"#,
+            r#""cas":"03596e1743f8d7e969979d5e4f9f8bf41bca02c723f84a2f12193b2196077805","#,
+            r#""pbkdf":{"alg":"argon2","params":"m=16,p=1,t=1"},"cipher":"aes-256-siv","#,
+            r#""segments":[]}]},"#,
+            r#"{"keyword":"Agent_007","state":"encrypted-cas","lines":[7,7],"#,
+            r#""cas":"b30ccd443bae74afc464822857fad6974f0cbb12197368494cc311441c74ea20","#,
+            r#""pbkdf":{"alg":"argon2","params":"m=16,p=1,t=1"},"cipher":"aes-256-siv","#,
+            r#""segments":[]}]}]"#,
+            "\n"
+        ));
+}
+
+#[test]
+fn ls_stored_size() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("store")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let stored = fs::read_to_string(&ept.path).unwrap();
+
+    let output = Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("ls")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(&ept.path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(listing.contains(
+        "  Agent_007 stored lines 7-7 \
+         cas 575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306 size 20\n"
+    ));
+    // listing doesn't modify anything
+    assert_eq!(fs::read_to_string(&ept.path).unwrap(), stored);
+}
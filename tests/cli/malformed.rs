@@ -0,0 +1,149 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+
+use Fixture;
+
+fn run(content: &[u8], args: &[&str]) -> assert_cmd::assert::Assert {
+    let ept = Fixture::blank("malformed.ept");
+    fs::write(&ept.path, content).unwrap();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(args)
+        .arg(&ept.path)
+        .arg("-o")
+        .arg("-")
+        .assert()
+}
+
+fn assert_rejected(content: &[u8], args: &[&str], err: &str) {
+    run(content, args)
+        .failure()
+        .stderr(predicate::str::contains(err))
+        .stderr(predicate::str::contains("panicked").not());
+}
+
+#[test]
+fn empty_directive() {
+    assert_rejected(b"text\n// <( )>\n", &[], "Empty directive");
+}
+
+#[test]
+fn unknown_section() {
+    assert_rejected(b"// <( BOGUS X )>\n", &[], "Unknown section 'BOGUS'");
+}
+
+#[test]
+fn missing_right_separator() {
+    assert_rejected(b"// <( BEGIN X\n", &[], "Right separator ')>' missing");
+}
+
+#[test]
+fn end_without_start() {
+    assert_rejected(b"// <( END X )>\n", &[], "END without a start clause");
+}
+
+#[test]
+fn end_mismatch() {
+    assert_rejected(
+        b"// <( BEGIN X )>\n// <( END Y )>\n",
+        &[],
+        "END mismatch (expected 'X')",
+    );
+}
+
+#[test]
+fn unterminated_begin() {
+    assert_rejected(b"// <( BEGIN X )>\nsecret\n", &[], "BEGIN X without END");
+}
+
+#[test]
+fn invalid_base64() {
+    assert_rejected(
+        b"// <( ENCRYPTED X )>\n// <( DATA !!!! )>\n// <( END X )>\n",
+        &[],
+        "Error decoding base64",
+    );
+}
+
+#[test]
+fn invalid_cas_id() {
+    assert_rejected(
+        b"// <( ENCRYPTED X abcd )>\n",
+        &[],
+        "Invalid CAS identifier 'abcd'",
+    );
+}
+
+#[test]
+fn empty_encrypted() {
+    assert_rejected(
+        b"// <( ENCRYPTED X )>\n// <( END X )>\n",
+        &[],
+        "0 elements in encrypted X",
+    );
+}
+
+#[test]
+fn bare_end_closes_encrypted() {
+    run(
+        b"// <( ENCRYPTED X )>\n// <( DATA AAAA )>\n// <( END )>\n",
+        &[],
+    )
+    .success();
+}
+
+#[test]
+fn excessive_nesting() {
+    let mut content = String::new();
+    for _ in 0..1000 {
+        content += "// <( BEGIN X )>\n";
+    }
+    for _ in 0..1000 {
+        content += "// <( END X )>\n";
+    }
+    assert_rejected(
+        content.as_bytes(),
+        &[],
+        "Maximum nesting depth (100) exceeded",
+    );
+}
+
+#[test]
+fn invalid_utf8() {
+    assert_rejected(
+        b"// <( BEGIN caf\xe9 )>\n// <( END )>\n",
+        &[],
+        "Invalid UTF-8 in directive",
+    );
+}
+
+#[test]
+fn invalid_pbkdf_params() {
+    assert_rejected(
+        b"// <( ENCRYPTED X pbkdf:$pbkdf2-sha256$i=lots$AQIDBAUGBwg )>\n\
+          // <( DATA AAAA )>\n\
+          // <( END X )>\n",
+        &["-d", "X", "-k", "X=password"],
+        "Invalid PHC parameter",
+    );
+}
+
+#[test]
+fn invalid_iv_length() {
+    assert_rejected(
+        b"// <( ENCRYPTED X cipher:aes-256-gcm-siv$iv=AAAA )>\n\
+          // <( DATA AAAA )>\n\
+          // <( END X )>\n",
+        &["-d", "X", "-k", "X=password"],
+        "Invalid key or IV length",
+    );
+}
+
+#[test]
+fn invalid_hidden_options() {
+    assert_rejected(b"", &["--pbkdf-params", "i"], "NAME=NUMBER");
+    assert_rejected(b"", &["--pbkdf-salt", "xyz"], "Expected a hex string");
+    assert_rejected(b"", &["--cipher-iv", "xyz"], "Expected a hex string");
+}
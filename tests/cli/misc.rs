@@ -4,6 +4,7 @@ use std::fs;
 use std::process::Command;
 
 use Fixture;
+use DATA_KEY_SIV;
 
 #[test]
 fn help_produces_usage() {
@@ -78,6 +79,8 @@ fn output() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .arg("-o")
         .arg(&output.path)
@@ -90,7 +93,7 @@ fn output() {
     );
     assert_eq!(
         &fs::read_to_string(&output.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap()
     );
 }
 
@@ -109,6 +112,8 @@ fn output_multiple() {
         .arg("legacy")
         .arg("-k")
         .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept1.path)
         .arg("-o")
         .arg(&out1.path)
@@ -130,15 +135,15 @@ fn output_multiple() {
     // these two have outputs specified
     assert_eq!(
         &fs::read_to_string(&out1.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap()
     );
     assert_eq!(
         &fs::read_to_string(&out2.path).unwrap(),
-        &fs::read_to_string("test-data/simple-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/simple-encrypt-agent007-v2.ept").unwrap()
     );
     // no output specified for this one, so the input is the output
     assert_eq!(
         &fs::read_to_string(&ept3.path).unwrap(),
-        &fs::read_to_string("test-data/simple-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/simple-encrypt-agent007-v2.ept").unwrap()
     );
 }
@@ -1,9 +1,28 @@
+mod access;
+mod bundle;
+mod cas_backend;
+mod cas_fsck;
+mod cas_hash;
+mod cas_key;
+mod cas_layout;
+mod cas_search;
 mod cipher;
+mod config;
+mod dry_run;
 mod encrypt_decrypt;
 mod encrypt_store;
+mod format_version;
+mod gc;
+mod in_place;
 mod issue_15;
+mod ls;
+mod malformed;
 mod misc;
 mod pbkdf;
 mod pipe;
 mod policy;
+mod recipient;
 mod store_fetch;
+mod subcommands;
+mod syntax;
+mod verify;
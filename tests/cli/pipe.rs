@@ -3,6 +3,7 @@ use std::fs;
 use std::process::Command;
 
 use Fixture;
+use DATA_KEY_SIV;
 
 #[test]
 fn pipe_test_passthrough_default() {
@@ -14,11 +15,13 @@ fn pipe_test_passthrough_default() {
         .arg("Agent_007=password")
         .arg("--pbkdf")
         .arg("legacy")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .with_stdin()
         .buffer(fs::read_to_string("sample/test.ept").unwrap())
         .assert()
         .success()
-        .stdout(fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap());
+        .stdout(fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap());
 }
 
 #[test]
@@ -31,12 +34,14 @@ fn pipe_test_passthrough() {
         .arg("Agent_007=password")
         .arg("--pbkdf")
         .arg("legacy")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg("-")
         .with_stdin()
         .buffer(fs::read_to_string("sample/test.ept").unwrap())
         .assert()
         .success()
-        .stdout(fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap());
+        .stdout(fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap());
 }
 
 #[test]
@@ -50,6 +55,8 @@ fn pipe_test_1() {
         .arg("Agent_007=password")
         .arg("--pbkdf")
         .arg("legacy")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg("-")
         .arg("-o")
         .arg(&out.path)
@@ -60,7 +67,7 @@ fn pipe_test_1() {
         .stdout("");
     assert_eq!(
         &fs::read_to_string(&out.path).unwrap(),
-        &fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap(),
+        &fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap(),
     );
 }
 
@@ -75,12 +82,14 @@ fn pipe_test_2() {
         .arg("Agent_007=password")
         .arg("--pbkdf")
         .arg("legacy")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg(&ept.path)
         .arg("-o")
         .arg("-")
         .assert()
         .success()
-        .stdout(fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap());
+        .stdout(fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap());
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
         &fs::read_to_string(&ept.source).unwrap()
@@ -98,15 +107,17 @@ fn pipe_test_3() {
         .arg("Agent_007=password")
         .arg("--pbkdf")
         .arg("legacy")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
         .arg("-")
         .arg(&ept.path)
         .with_stdin()
         .buffer(fs::read_to_string("sample/test.ept").unwrap())
         .assert()
         .success()
-        .stdout(fs::read_to_string("test-data/test-encrypt-agent007.ept").unwrap());
+        .stdout(fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap());
     assert_eq!(
         &fs::read_to_string(&ept.path).unwrap(),
-        &fs::read_to_string("test-data/simple-encrypt-agent007.ept").unwrap()
+        &fs::read_to_string("test-data/simple-encrypt-agent007-v2.ept").unwrap()
     );
 }
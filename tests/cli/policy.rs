@@ -195,7 +195,7 @@ fn fips_flag() {
         .assert()
         .success()
         .stdout(
-            predicates::str::contains("pbkdf:$pbkdf2-sha512$")
+            predicates::str::contains("password.0:$pbkdf2-sha512$")
                 .and(predicates::str::contains("cipher:aes-256-gcm$iv=")),
         );
 
@@ -0,0 +1,284 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+fn enprot(casdir: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("-c").arg(casdir);
+    cmd
+}
+
+fn keygen(path: &Path) -> String {
+    let output = Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["keygen", "-o"])
+        .arg(path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+#[test]
+fn recipient_keygen() {
+    let dir = tempdir().unwrap();
+    let id = dir.path().join("id.txt");
+    let public = keygen(&id);
+    assert!(public.starts_with("x25519:"));
+    let identity = fs::read_to_string(&id).unwrap();
+    assert!(identity.contains(&format!("# public key: {}", public)));
+    assert!(identity.contains("x25519-secret:"));
+
+    // an existing identity is never overwritten
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .args(["keygen", "-o"])
+        .arg(&id)
+        .assert()
+        .failure();
+    assert_eq!(fs::read_to_string(&id).unwrap(), identity);
+
+    // without a file the identity goes to stdout
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("keygen")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("x25519-secret:"));
+}
+
+#[test]
+fn recipient_encrypt_decrypt() {
+    let casdir = tempdir().unwrap();
+    let keys = tempdir().unwrap();
+    let (id1, id2, id3) = (
+        keys.path().join("id1"),
+        keys.path().join("id2"),
+        keys.path().join("id3"),
+    );
+    let (pub1, pub2) = (keygen(&id1), keygen(&id2));
+    keygen(&id3);
+
+    let ept = Fixture::copy("sample/test.ept");
+    enprot(casdir.path())
+        .args([
+            "-e",
+            "Agent_007",
+            "--recipient",
+            &pub1,
+            "--recipient",
+            &pub2,
+        ])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let encrypted = fs::read_to_string(&ept.path).unwrap();
+    assert!(encrypted.contains("recipient.1:x25519$"));
+    assert!(!encrypted.contains("pbkdf:"));
+
+    for id in &[&id1, &id2] {
+        fs::write(&ept.path, &encrypted).unwrap();
+        enprot(casdir.path())
+            .args(["-d", "Agent_007", "-i"])
+            .arg(id)
+            .arg(&ept.path)
+            .assert()
+            .success();
+        assert_eq!(
+            &fs::read_to_string(&ept.path).unwrap(),
+            &fs::read_to_string(&ept.source).unwrap()
+        );
+    }
+
+    fs::write(&ept.path, &encrypted).unwrap();
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-i"])
+        .arg(&id3)
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "No identity matches a recipient of the segment",
+        ));
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn recipient_with_password() {
+    let casdir = tempdir().unwrap();
+    let keys = tempdir().unwrap();
+    let id = keys.path().join("id");
+    let public = keygen(&id);
+
+    let ept = Fixture::copy("sample/test.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=password"])
+        .args(["--recipient", &public])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    let encrypted = fs::read_to_string(&ept.path).unwrap();
+
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=wrong"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Wrong password for the data key"));
+    for args in &[["-k", "Agent_007=password"], ["-i", id.to_str().unwrap()]] {
+        fs::write(&ept.path, &encrypted).unwrap();
+        enprot(casdir.path())
+            .args(["-d", "Agent_007"])
+            .args(args)
+            .arg(&ept.path)
+            .assert()
+            .success();
+        assert_eq!(
+            &fs::read_to_string(&ept.path).unwrap(),
+            &fs::read_to_string(&ept.source).unwrap()
+        );
+    }
+
+    // the legacy PBKDF wraps the data key as well
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "-k", "Agent_007=password"])
+        .args(["--pbkdf", "legacy", "--recipient", &public])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&ept.path)
+        .unwrap()
+        .contains("password.0:$legacy$$"));
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-k", "Agent_007=password"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn recipient_verify() {
+    let casdir = tempdir().unwrap();
+    let keys = tempdir().unwrap();
+    let id = keys.path().join("id");
+    let public = keygen(&id);
+
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "--recipient", &public])
+        .args(["--cipher", "aes-256-gcm"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("verify")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-i")
+        .arg(&id)
+        .arg(&ept.path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ok, authenticated"));
+}
+
+#[test]
+fn recipient_config() {
+    let casdir = tempdir().unwrap();
+    let keys = tempdir().unwrap();
+    let id = keys.path().join("id");
+    let public = keygen(&id);
+
+    let ept = Fixture::copy("sample/simple.ept");
+    fs::write(
+        ept.path.parent().unwrap().join(".enprot.toml"),
+        format!("recipients = [\"{}\"]\n", public),
+    )
+    .unwrap();
+    enprot(casdir.path())
+        .args(["-e", "Agent_007"])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'recipients' is only accepted with --trust-config",
+        ));
+    enprot(casdir.path())
+        .args(["--trust-config", "-e", "Agent_007"])
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&ept.path)
+        .unwrap()
+        .contains("recipient.0:"));
+    enprot(casdir.path())
+        .args(["--trust-config", "-d", "Agent_007", "-i"])
+        .arg(&id)
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn recipient_invalid() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args(["-e", "Agent_007", "--recipient", "x25519:AAAA"])
+        .arg(&ept.path)
+        .assert()
+        .failure();
+
+    let keys = tempdir().unwrap();
+    let id = keys.path().join("id");
+    fs::write(&id, "# nothing here\n").unwrap();
+    enprot(casdir.path())
+        .args(["-d", "Agent_007", "-i"])
+        .arg(&id)
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no secret keys"));
+}
+
+#[test]
+fn recipient_policy_nist() {
+    let casdir = tempdir().unwrap();
+    let keys = tempdir().unwrap();
+    let public = keygen(&keys.path().join("id"));
+
+    let ept = Fixture::copy("sample/simple.ept");
+    enprot(casdir.path())
+        .args([
+            "-e",
+            "Agent_007",
+            "--policy",
+            "nist",
+            "--recipient",
+            &public,
+        ])
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Key agreement algorithm is not permitted by policy",
+        ));
+}
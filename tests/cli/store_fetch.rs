@@ -240,3 +240,30 @@ fn store_fetch_alt_sep() {
         &fs::read_to_string(&ept.path).unwrap()
     );
 }
+
+#[test]
+fn store_repairs_corrupt_object() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+    let object = casdir
+        .path()
+        .join("575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306");
+
+    // a truncated object, as left behind by an interrupted write
+    fs::write(&object, "Super sec").unwrap();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-s")
+        .arg("Agent_007")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&object).unwrap(),
+        "Super secret line 3\n"
+    );
+    // no temporary files are left in the CAS
+    assert_eq!(fs::read_dir(casdir.path()).unwrap().count(), 2);
+}
@@ -0,0 +1,182 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+use DATA_KEY_SIV;
+
+#[test]
+fn subcommand_encrypt_decrypt() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("encrypt")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("--pbkdf")
+        .arg("legacy")
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string("test-data/test-encrypt-agent007-v2.ept").unwrap()
+    );
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("decrypt")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn subcommand_encrypt_store() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("encrypt")
+        .arg("--store")
+        .arg("Agent_007,GEHEIM")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("--pbkdf")
+        .arg("legacy")
+        .arg("-k")
+        .arg("Agent_007=password,GEHEIM=password")
+        .arg("--data-key")
+        .arg(DATA_KEY_SIV)
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string("test-data/test-encrypt-store-both-v2.ept").unwrap()
+    );
+}
+
+#[test]
+fn subcommand_store_fetch() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("store")
+        .arg("GEHEIM")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&ept.path)
+        .unwrap()
+        .contains("// <( STORED GEHEIM "));
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("fetch")
+        .arg("GEHEIM")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn subcommand_rekey() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("encrypt")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("--pbkdf")
+        .arg("legacy")
+        .arg("-k")
+        .arg("Agent_007=old")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("rekey")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("--pbkdf")
+        .arg("legacy")
+        .arg("-k")
+        .arg("Agent_007=old")
+        .arg("-n")
+        .arg("Agent_007=new")
+        .arg(&ept.path)
+        .assert()
+        .success();
+
+    // the old password no longer works
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("decrypt")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-k")
+        .arg("Agent_007=old")
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Error decrypting Agent_007"));
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("decrypt")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg("-k")
+        .arg("Agent_007=new")
+        .arg(&ept.path)
+        .assert()
+        .success();
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string(&ept.source).unwrap()
+    );
+}
+
+#[test]
+fn subcommand_requires_words() {
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("decrypt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("<WORD[,WORD...]>"));
+}
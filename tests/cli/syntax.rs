@@ -0,0 +1,157 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+fn write_block(path: &Path, left: &str, right: &str) {
+    fs::write(
+        path,
+        format!(
+            "{l} BEGIN Agent_007 {r}\nsecret\n{l} END Agent_007 {r}\n",
+            l = left,
+            r = right
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn syntax_auto_by_extension() {
+    let dir = tempdir().unwrap();
+    let files = [
+        ("main.rs", "// <(", ")>"),
+        ("setup.py", "# <(", ")>"),
+        ("README.md", "<!-- <(", ")> -->"),
+        ("paper.tex", "% <(", ")>"),
+        ("notes.txt", "<(", ")>"),
+    ];
+    for (name, left, right) in files.iter() {
+        write_block(&dir.path().join(name), left, right);
+    }
+
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("--syntax")
+        .arg("auto")
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg("-e")
+        .arg("Agent_007");
+    for (name, _, _) in files.iter() {
+        cmd.arg(dir.path().join(name));
+    }
+    cmd.assert().success();
+
+    for (name, left, right) in files.iter() {
+        let text = fs::read_to_string(dir.path().join(name)).unwrap();
+        assert!(text.starts_with(&format!("{} ENCRYPTED Agent_007 ", left)));
+        assert!(text.ends_with(&format!("{} END Agent_007 {}\n", left, right)));
+        assert!(!text.contains("secret"));
+    }
+
+    let mut cmd = Command::cargo_bin("enprot").unwrap();
+    cmd.arg("--syntax")
+        .arg("auto")
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg("-d")
+        .arg("Agent_007");
+    for (name, _, _) in files.iter() {
+        cmd.arg(dir.path().join(name));
+    }
+    cmd.assert().success();
+
+    for (name, left, right) in files.iter() {
+        let text = fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(
+            text,
+            format!(
+                "{l} BEGIN Agent_007 {r}\nsecret\n{l} END Agent_007 {r}\n",
+                l = left,
+                r = right
+            )
+        );
+    }
+}
+
+#[test]
+fn syntax_auto_falls_back_to_separators() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("data.unknown");
+    write_block(&path, "{", "}");
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("--syntax")
+        .arg("auto")
+        .arg("-l")
+        .arg("{")
+        .arg("-r")
+        .arg("}")
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg("-e")
+        .arg("Agent_007")
+        .arg(&path)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .starts_with("{ ENCRYPTED Agent_007 "));
+}
+
+#[test]
+fn syntax_auto_explicit_separator_wins() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("data.c");
+    write_block(&path, "// <(", "]]");
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("--syntax")
+        .arg("auto")
+        .arg("-r")
+        .arg("]]")
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg("-e")
+        .arg("Agent_007")
+        .arg(&path)
+        .assert()
+        .success();
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("// <( ENCRYPTED Agent_007 "));
+    assert!(text.lines().next().unwrap().ends_with("]]"));
+}
+
+#[test]
+fn syntax_preset() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("script");
+    write_block(&path, "# <(", ")>");
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("--syntax")
+        .arg("hash")
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg("-e")
+        .arg("Agent_007")
+        .arg(&path)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .starts_with("# <( ENCRYPTED Agent_007 "));
+}
+
+#[test]
+fn syntax_invalid() {
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("--syntax")
+        .arg("cobol")
+        .arg("-")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("isn't a valid value"));
+}
@@ -0,0 +1,143 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+
+use Fixture;
+
+#[test]
+fn verify_stored() {
+    let casdir = tempdir().unwrap();
+    let ept = Fixture::copy("sample/test.ept");
+
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("store")
+        .arg("Agent_007")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(&ept.path)
+        .assert()
+        .success();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("verify")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(&ept.path)
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:
+  GEHEIM plain lines 2-6: ok
+    Agent_007 stored lines 5-5 cas d094e230861eb0ab43b895b8ecdeeb9e3a7e4a88239341a81da832ac181feaab: ok
+  Agent_007 stored lines 7-7 cas 575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306: ok
+",
+            ept.path.display()
+        ));
+    // the file is left alone
+    assert_eq!(
+        &fs::read_to_string(&ept.path).unwrap(),
+        &fs::read_to_string("test-data/test-store-agent007.ept").unwrap()
+    );
+
+    // a missing and a corrupted object
+    fs::remove_file(
+        casdir
+            .path()
+            .join("d094e230861eb0ab43b895b8ecdeeb9e3a7e4a88239341a81da832ac181feaab"),
+    )
+    .unwrap();
+    fs::write(
+        casdir
+            .path()
+            .join("575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306"),
+        "tampered\n",
+    )
+    .unwrap();
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("verify")
+        .arg("-c")
+        .arg(casdir.path())
+        .arg(&ept.path)
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::contains(
+                "Agent_007 stored lines 5-5 cas d094e230861eb0ab43b895b8ecdeeb9e3a7e4a88239341a81da832ac181feaab: FAILED: CAS error",
+            )
+            .and(predicate::str::contains(
+                "Agent_007 stored lines 7-7 cas 575d69f5b0034279bc3ef164e94287e6366e9df76729895a302a66a8817cf306: FAILED: CAS content hash mismatch",
+            )),
+        );
+}
+
+#[test]
+fn verify_authenticate() {
+    let ept = "test-data/test-encrypt-agent007-v2.ept";
+
+    // without keys only the structure is checked
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("verify")
+        .arg(ept)
+        .assert()
+        .success()
+        .stdout(
+            "test-data/test-encrypt-agent007-v2.ept:
+  GEHEIM plain lines 2-8: ok
+    Agent_007 encrypted-inline lines 5-7: ok, not authenticated (no key)
+  Agent_007 encrypted-inline lines 9-11: ok, not authenticated (no key)
+",
+        );
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("verify")
+        .arg("-k")
+        .arg("Agent_007=password")
+        .arg(ept)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  Agent_007 encrypted-inline lines 9-11: ok, authenticated\n",
+        ));
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("verify")
+        .arg("-k")
+        .arg("Agent_007=wrong")
+        .arg(ept)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "  Agent_007 encrypted-inline lines 9-11: FAILED: ",
+        ));
+}
+
+#[test]
+fn verify_malformed() {
+    let ept = Fixture::blank("malformed.ept");
+    fs::write(&ept.path, "// <( BEGIN X )>\n// <( END Y )>\n").unwrap();
+
+    // the report goes on with the remaining files
+    Command::cargo_bin("enprot")
+        .unwrap()
+        .arg("verify")
+        .arg(&ept.path)
+        .arg("sample/test.ept")
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::contains(format!("{}: FAILED: ", ept.path.display()))
+                .and(predicate::str::contains("END mismatch"))
+                .and(predicate::str::ends_with(
+                    "sample/test.ept:
+  GEHEIM plain lines 2-8: ok
+    Agent_007 plain lines 5-7: ok
+  Agent_007 plain lines 9-11: ok
+",
+                )),
+        );
+}
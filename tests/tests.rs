@@ -2,7 +2,9 @@
 
 extern crate assert_cmd;
 extern crate cpu_time;
+extern crate enprot;
 extern crate predicates;
+extern crate proptest;
 extern crate tempfile;
 
 use std::env;
@@ -11,6 +13,7 @@ use std::path::Path;
 use std::path::PathBuf;
 use tempfile::TempDir;
 
+mod api;
 mod cli;
 
 struct Fixture {
@@ -41,13 +44,21 @@ impl Fixture {
     }
 }
 
-pub fn digest(alg: &str, data: &[u8]) -> Result<Vec<u8>, &'static str> {
+// data keys for --data-key, which makes encrypting repeatable, of aes-256-siv
+// and of the 256-bit ciphers
+pub const DATA_KEY_SIV: &str = concat!(
+    "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+    "202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f"
+);
+pub const DATA_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+pub fn digest(alg: &str, data: &[u8]) -> Result<Vec<u8>, enprot::EnprotError> {
     let policy: Box<dyn enprot::crypto::CryptoPolicy> =
         Box::new(enprot::crypto::CryptoPolicyDefault {});
     enprot::crypto::digest(alg, data, &policy)
 }
 
-pub fn hexdigest(alg: &str, data: &[u8]) -> Result<String, &'static str> {
+pub fn hexdigest(alg: &str, data: &[u8]) -> Result<String, enprot::EnprotError> {
     let policy: Box<dyn enprot::crypto::CryptoPolicy> =
         Box::new(enprot::crypto::CryptoPolicyDefault {});
     enprot::crypto::hexdigest(alg, data, &policy)